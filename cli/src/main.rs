@@ -1,21 +1,15 @@
 extern crate core;
 
-use anyhow::Error;
 use clap::Parser;
+use client::{Address, Connection, Options, TlsOptions};
 use core::command::Command;
+use core::response::ErrorCode;
 use core::response::Response;
 use core::response::Status;
-use core::serializer::CommandSerializer;
-use core::serializer::ProtoCommandSerializer;
-use core::serializer::ResponseSerializer;
 use std::io::stdin;
-use std::net::TcpStream;
 use std::process::ExitCode;
 use std::str::FromStr;
 
-mod command;
-use std::io::{Read, Write};
-
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct CliArgs {
@@ -25,42 +19,91 @@ struct CliArgs {
     /// Port to connect to
     #[clap(long, default_value = "5880")]
     port: u16,
+    /// Unix domain socket to connect to, instead of --host/--port
+    #[clap(long, value_name = "PATH", conflicts_with_all = ["host", "port", "tls", "ca_cert"])]
+    socket: Option<String>,
+    /// Connect using TLS
+    #[clap(long)]
+    tls: bool,
+    /// PEM file with the CA used to verify the server certificate. Implies --tls
+    #[clap(long, value_name = "FILE")]
+    ca_cert: Option<String>,
+    /// PEM client certificate, for servers that require mTLS. Requires --key
+    #[clap(long, value_name = "FILE", requires = "key")]
+    cert: Option<String>,
+    /// PEM private key of the client certificate. Requires --cert
+    #[clap(long, value_name = "FILE", requires = "cert")]
+    key: Option<String>,
+    /// User to authenticate as
+    #[clap(long, env = "TUNA_USER")]
+    user: Option<String>,
+    /// Password of the user
+    #[clap(long, env = "TUNA_PASSWORD", hide_env_values = true, requires = "user")]
+    password: Option<String>,
 }
 
-// TODO: refactor
-fn new_command_serializer() -> Box<dyn CommandSerializer> {
-    Box::new(ProtoCommandSerializer)
-}
-
-// TODO: refactor
-fn new_response_serializer() -> Box<dyn ResponseSerializer> {
-    Box::new(core::serializer::ProtoResponseSerializer)
+/// Options of the connection described by the command line arguments.
+fn options(args: &CliArgs) -> Options {
+    let mut options = match &args.socket {
+        Some(socket) => Options::unix(socket),
+        // IPv6 addresses are written in brackets when followed by a port
+        None if args.host.contains(':') && !args.host.starts_with('[') => {
+            Options::tcp(format!("[{}]:{}", args.host, args.port))
+        }
+        None => Options::tcp(format!("{}:{}", args.host, args.port)),
+    };
+    if args.tls || args.ca_cert.is_some() || args.cert.is_some() {
+        options.tls = Some(TlsOptions {
+            ca_cert: args.ca_cert.as_ref().map(Into::into),
+            cert: args.cert.as_ref().map(Into::into),
+            key: args.key.as_ref().map(Into::into),
+        });
+    }
+    options.user = args.user.clone();
+    options.password = args.password.clone();
+    options
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    let args = CliArgs::parse();
-
-    let command_serializer = new_command_serializer();
-    let response_serializer = new_response_serializer();
+    let options = options(&CliArgs::parse());
 
-    println!("Connecting to {}:{}...", args.host, args.port);
-    let mut stream = match TcpStream::connect(format!("{}:{}", args.host, args.port).as_str()) {
-        Ok(stream) => stream,
+    match &options.address {
+        Address::Unix(socket) => println!("Connecting to {}...", socket.display()),
+        Address::Tcp(address) => println!("Connecting to {}...", address),
+    }
+    let mut connection = match Connection::connect(&options).await {
+        Ok(connection) => connection,
+        Err(e) if e.code() == Some(ErrorCode::Unauthorized) => {
+            println!("Couldn't authenticate: {}", e);
+            return ExitCode::from(1);
+        }
         Err(e) => {
             println!("Couldn't connect to server: {}", e);
             return ExitCode::from(1);
         }
     };
 
+    match connection.hello() {
+        Some(hello) => println!(
+            "Server version {} (protocol version {})",
+            hello.server_version, hello.protocol_version
+        ),
+        None => println!("The server doesn't support protocol version negotiation"),
+    }
+
     println!("Connected to server. Type 'help' for a list of commands.");
 
+    // Commands queued since MULTI, to print the results of EXEC
+    let mut queued: Option<Vec<Command>> = None;
+
     loop {
         // Read command (TODO: refactor)
         let mut buffer = String::new();
         let _ = stdin().read_line(&mut buffer);
 
         if buffer.trim() == "exit" {
+            let _ = connection.shutdown().await;
             println!("bye!");
             break;
         }
@@ -78,15 +121,26 @@ async fn main() -> ExitCode {
             }
         };
 
-        // Send command
-        if let Err(e) = send_command(&cmd, &command_serializer, &mut stream) {
-            println!("error: {}", e);
-            continue;
-        }
-
-        // Read response
-        match read_response(&mut stream, &response_serializer) {
-            Ok(response) => print_response(cmd, response),
+        let subscribing = matches!(
+            cmd,
+            Command::Subscribe { .. } | Command::Psubscribe { .. } | Command::Cdc { .. }
+        );
+        match connection.request(&cmd).await {
+            Ok(response) => {
+                let subscribed = matches!(response.status, Status::Ok);
+                let cmd = match track_transaction(&mut queued, cmd, &response) {
+                    Some(cmd) => cmd,
+                    None => continue,
+                };
+                match cmd {
+                    Command::Exec => print_results(queued.take().unwrap_or_default(), response),
+                    cmd => print_response(cmd, response),
+                }
+                if subscribing && subscribed {
+                    listen(&mut connection).await;
+                    return ExitCode::from(0);
+                }
+            }
             Err(e) => println!("error: {}", e),
         }
     }
@@ -100,28 +154,98 @@ fn print_help() {
     println!("  set <key> <value");
     println!("  del <key>");
     println!("  list");
+    println!("  auth <user> <password>");
+    println!("  ping [message]");
+    println!("  subscribe <channel> [channel ...]");
+    println!("  psubscribe <pattern> [pattern ...]");
+    println!("  publish <channel> <message>");
+    println!("  cdc [position]");
+    println!("  wait <key> [timeout_ms] [changed]");
+    println!("  multi");
+    println!("  exec");
+    println!("  discard");
+    println!("  watch <key> [key ...]");
+    println!("  unwatch");
+    println!("  hello [version]");
+    println!("  info");
+    println!("  dbsize");
+    println!("  flushdb");
+    println!("  config get [pattern]");
+    println!("  config set <parameter> <value>");
+    println!("  config rewrite");
+    println!("  slowlog get [count]");
+    println!("  slowlog reset");
+    println!("  slowlog len");
     println!("  exit");
 }
 
-fn send_command(
-    cmd: &Command,
-    serializer: &Box<dyn CommandSerializer>,
-    stream: &mut TcpStream,
-) -> Result<(), Error> {
-    let mut buf = Vec::new();
-    buf.reserve(serializer.encoded_len(&cmd));
-    serializer.encode(cmd, &mut buf)?;
-    stream.write(&buf)?;
-    Ok(())
+/// Prints the messages and changes pushed to a subscribed connection until
+/// it's closed.
+async fn listen(connection: &mut Connection) {
+    println!("Waiting for messages, press Ctrl-C to quit.");
+    loop {
+        let response = match connection.receive().await {
+            Ok(response) => response,
+            Err(e) => {
+                println!("error: {}", e);
+                return;
+            }
+        };
+        for change in &response.changes {
+            match &change.value {
+                Some(value) => println!("#{} set {} {}", change.sequence, change.key, value),
+                None => println!("#{} del {}", change.sequence, change.key),
+            }
+        }
+        match response.message {
+            Some(message) => match message.pattern {
+                Some(pattern) => println!("[{}] {}: {}", pattern, message.channel, message.payload),
+                None => println!("{}: {}", message.channel, message.payload),
+            },
+            None => {
+                if let Some(error) = response.error {
+                    println!("error: {}", error);
+                    return;
+                }
+            }
+        }
+    }
 }
 
-fn read_response(
-    stream: &mut TcpStream,
-    serializer: &Box<dyn ResponseSerializer>,
-) -> Result<Response, Error> {
-    let mut response_bytes = [0; 128];
-    let n = stream.read(&mut response_bytes)?;
-    Ok(serializer.decode(&response_bytes[..n])?)
+/// Keeps track of the commands queued in a transaction. Returns the command
+/// back unless it was queued.
+fn track_transaction(
+    queued: &mut Option<Vec<Command>>,
+    cmd: Command,
+    response: &Response,
+) -> Option<Command> {
+    let ok = matches!(response.status, Status::Ok);
+    match (&cmd, queued.as_mut()) {
+        (Command::Multi, None) if ok => *queued = Some(Vec::new()),
+        (Command::Discard, Some(_)) if ok => *queued = None,
+        (Command::Exec, _) | (Command::Discard, _) | (Command::Multi, _) => {}
+        (_, Some(commands)) if ok && response.content.as_deref() == Some("QUEUED") => {
+            println!("QUEUED");
+            commands.push(cmd);
+            return None;
+        }
+        _ => {}
+    }
+    Some(cmd)
+}
+
+/// Prints the results of EXEC, next to the commands they belong to.
+fn print_results(commands: Vec<Command>, response: Response) {
+    if !matches!(response.status, Status::Ok) {
+        return print_response(Command::Exec, response);
+    }
+    if response.results.is_empty() {
+        println!("(empty list)");
+    }
+    for (i, (cmd, result)) in commands.into_iter().zip(response.results).enumerate() {
+        print!("{}) ", i + 1);
+        print_response(cmd, result);
+    }
 }
 
 fn print_response(command: Command, response: Response) {
@@ -130,7 +254,7 @@ fn print_response(command: Command, response: Response) {
             println!("UNSPECIFIED");
         }
         Status::Ok => match command {
-            Command::Get { .. } => match response.content {
+            Command::Get { .. } | Command::Wait { .. } => match response.content {
                 Some(content) => {
                     println!("{}", content);
                 }
@@ -144,21 +268,106 @@ fn print_response(command: Command, response: Response) {
             Command::Del { .. } => {
                 println!("ok");
             }
-            Command::List => match response.content {
-                Some(content) => {
-                    println!("{}", content);
+            Command::Auth { .. } => {
+                println!("ok");
+            }
+            Command::Ping { .. } => {
+                println!("{}", response.content.unwrap_or_default());
+            }
+            Command::Subscribe { .. }
+            | Command::Unsubscribe { .. }
+            | Command::Psubscribe { .. }
+            | Command::Punsubscribe { .. } => {
+                println!("subscribed to {}", response.integer.unwrap_or_default());
+            }
+            Command::Publish { .. } => {
+                println!("(receivers) {}", response.integer.unwrap_or_default());
+            }
+            Command::Hello { .. } => {
+                if let Some(hello) = response.hello {
+                    println!("server version: {}", hello.server_version);
+                    println!("protocol version: {}", hello.protocol_version);
+                    println!("features: {}", hello.features.join(", "));
                 }
-                None => {
-                    println!("(nil)");
+            }
+            Command::Info => {
+                if let Some(info) = response.info {
+                    println!("server version: {}", info.server_version);
+                    println!("uptime: {}s", info.uptime_secs);
+                    println!("engine: {}", info.engine);
+                    println!("file size: {} bytes", info.file_size);
+                    println!("keys: {}", info.keys);
+                    println!("connected clients: {}", info.connected_clients);
+                    match info.memory_bytes {
+                        Some(memory_bytes) => println!("memory: {} bytes", memory_bytes),
+                        None => println!("memory: unknown"),
+                    }
                 }
-            },
+            }
+            Command::Dbsize => {
+                println!("(keys) {}", response.integer.unwrap_or_default());
+            }
+            Command::Flushdb => {
+                println!("(deleted) {}", response.integer.unwrap_or_default());
+            }
+            Command::ConfigGet { .. } => {
+                if response.parameters.is_empty() {
+                    println!("(empty list)");
+                }
+                for (name, value) in response.parameters {
+                    println!("{} = {}", name, value);
+                }
+            }
+            Command::SlowlogGet { .. } => {
+                if response.slowlog.is_empty() {
+                    println!("(empty list)");
+                }
+                for entry in response.slowlog {
+                    let command = match entry.key {
+                        Some(key) => format!("{} {}", entry.command, key),
+                        None => entry.command,
+                    };
+                    println!(
+                        "{}) {} {}us {} {}",
+                        entry.id,
+                        entry.timestamp,
+                        entry.duration.as_micros(),
+                        entry.client,
+                        command
+                    );
+                }
+            }
+            Command::SlowlogLen => {
+                println!("(entries) {}", response.integer.unwrap_or_default());
+            }
+            Command::Cdc { .. }
+            | Command::ConfigSet { .. }
+            | Command::ConfigRewrite
+            | Command::SlowlogReset
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch { .. }
+            | Command::Unwatch => {
+                println!("ok");
+            }
+            Command::List => {
+                let mut keys = response.keys;
+                keys.sort();
+                if keys.is_empty() {
+                    println!("(empty list)");
+                }
+                for (i, key) in keys.iter().enumerate() {
+                    println!("{}) {}", i + 1, key);
+                }
+            }
         },
-        Status::Error => match response.content {
-            Some(content) => {
-                println!("error: {}", content);
+        Status::Error => match response.error {
+            Some(error) => {
+                println!("error: {}", error);
             }
             None => {
-                println!("(nil)");
+                println!("error: unknown");
             }
         },
         Status::NotFound => {
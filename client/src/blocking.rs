@@ -0,0 +1,52 @@
+use core::protocol::Hello;
+use tokio::runtime::Runtime;
+
+use crate::error::Result;
+use crate::options::Options;
+
+/**
+* Blocking version of `crate::Client`, for code that doesn't run in a Tokio
+* runtime. It drives the async client on a runtime of its own, so it must
+* not be used from async code.
+*/
+pub struct Client {
+    inner: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    pub fn connect(options: Options) -> Result<Client> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let inner = runtime.block_on(crate::Client::connect(options))?;
+        Ok(Client { inner, runtime })
+    }
+
+    /// Returns the value of the key, `None` if it doesn't exist.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        self.runtime.block_on(self.inner.get(key))
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.set(key, value))
+    }
+
+    pub fn del(&self, key: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.del(key))
+    }
+
+    /// Returns every key, in no particular order.
+    pub fn list(&self) -> Result<Vec<String>> {
+        self.runtime.block_on(self.inner.list())
+    }
+
+    pub fn ping(&self) -> Result<()> {
+        self.runtime.block_on(self.inner.ping())
+    }
+
+    /// Reply of the server to the handshake, `None` if the server predates it.
+    pub fn server_info(&self) -> Result<Option<Hello>> {
+        self.runtime.block_on(self.inner.server_info())
+    }
+}
@@ -0,0 +1,179 @@
+use std::future::Future;
+use std::io;
+
+use core::command::{Command, Secret};
+use core::frame;
+use core::protocol::{self, Hello};
+use core::response::{ErrorCode, Response, Status};
+use core::serializer::{
+    CommandSerializer, ProtoCommandSerializer, ProtoResponseSerializer, ResponseSerializer,
+};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+
+use crate::error::{Error, Result};
+use crate::options::{Address, Options};
+use crate::tls;
+
+/// Any bidirectional stream the client can talk to the server through.
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/**
+* Single connection to the server, ready to run commands.
+*
+* `Client` runs every command on a pooled connection of its own, so it can't
+* hold state on the server. A `Connection` can, for transactions,
+* subscriptions and CDC: its requests aren't timed out nor retried, as
+* the state would be lost with the connection.
+*/
+pub struct Connection {
+    stream: Box<dyn Stream>,
+    /// Server reply to HELLO, `None` if the server predates it
+    pub(crate) hello: Option<Hello>,
+    max_response_size: usize,
+}
+
+impl Connection {
+    /// Opens a connection speaking protobuf, negotiating the protocol version
+    /// and authenticating like the connections of `Client`.
+    pub async fn connect(options: &Options) -> Result<Connection> {
+        Connection::open(options, &ProtoCommandSerializer, &ProtoResponseSerializer).await
+    }
+
+    /// Reply of the server to the handshake, `None` if the server predates it.
+    pub fn hello(&self) -> Option<&Hello> {
+        self.hello.as_ref()
+    }
+
+    /// Sends the command and waits for its response. Error responses are
+    /// returned as they are, not as `Error::Server`.
+    pub async fn request(&mut self, command: &Command) -> Result<Response> {
+        self.run(
+            &encode(&ProtoCommandSerializer, command)?,
+            &ProtoResponseSerializer,
+        )
+        .await
+    }
+
+    /// Waits for the next response pushed by the server, such as the
+    /// messages of subscriptions and the changes followed with CDC.
+    pub async fn receive(&mut self) -> Result<Response> {
+        self.read(&ProtoResponseSerializer).await
+    }
+
+    /// Closes the connection, letting TLS connections send their
+    /// close_notify alert.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        Ok(self.stream.shutdown().await?)
+    }
+
+    /// Connects, negotiates the protocol version and authenticates if the
+    /// options have credentials.
+    pub(crate) async fn open(
+        options: &Options,
+        command_serializer: &dyn CommandSerializer,
+        response_serializer: &dyn ResponseSerializer,
+    ) -> Result<Connection> {
+        with_timeout(options.connect_timeout, async {
+            let stream: Box<dyn Stream> = match &options.address {
+                Address::Tcp(address) => {
+                    let socket = TcpStream::connect(address).await?;
+                    match &options.tls {
+                        Some(tls_options) => {
+                            Box::new(tls::connect(tls_options, address, socket).await?)
+                        }
+                        None => Box::new(socket),
+                    }
+                }
+                Address::Unix(path) => Box::new(UnixStream::connect(path).await?),
+            };
+            let mut connection = Connection {
+                stream,
+                hello: None,
+                max_response_size: options.max_response_size,
+            };
+
+            let hello = Command::Hello {
+                version: protocol::PROTOCOL_VERSION,
+                features: Vec::new(),
+            };
+            let response = connection
+                .run(&encode(command_serializer, &hello)?, response_serializer)
+                .await?;
+            connection.hello = match (response.hello, response.error) {
+                (Some(hello), _) => Some(hello),
+                (None, Some(e)) if e.code == ErrorCode::IncompatibleProtocol => {
+                    return Err(Error::Server(e))
+                }
+                _ => None,
+            };
+
+            if let Some(user) = &options.user {
+                let auth = Command::Auth {
+                    user: user.to_string(),
+                    password: Secret(options.password.clone().unwrap_or_default()),
+                };
+                let response = connection
+                    .run(&encode(command_serializer, &auth)?, response_serializer)
+                    .await?;
+                into_result(response)?;
+            }
+            Ok(connection)
+        })
+        .await
+    }
+
+    /// Sends an encoded command and waits for its response.
+    pub(crate) async fn run(
+        &mut self,
+        command: &[u8],
+        response_serializer: &dyn ResponseSerializer,
+    ) -> Result<Response> {
+        frame::write_frame(&mut self.stream, command).await?;
+        self.read(response_serializer).await
+    }
+
+    async fn read(&mut self, response_serializer: &dyn ResponseSerializer) -> Result<Response> {
+        let bytes = frame::read_frame(&mut self.stream, self.max_response_size)
+            .await?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed by the server",
+                )
+            })?;
+        response_serializer
+            .decode(&bytes)
+            .map_err(|e| Error::Protocol(e.to_string()))
+    }
+}
+
+pub(crate) fn encode(serializer: &dyn CommandSerializer, command: &Command) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(serializer.encoded_len(command));
+    serializer
+        .encode(command, &mut buf)
+        .map_err(|e| Error::Protocol(e.to_string()))?;
+    Ok(buf)
+}
+
+/// Turns error responses into errors.
+pub(crate) fn into_result(response: Response) -> Result<Response> {
+    match response.status {
+        Status::Error => Err(match response.error {
+            Some(e) => Error::Server(e),
+            None => Error::Protocol("error response without an error".to_string()),
+        }),
+        _ => Ok(response),
+    }
+}
+
+pub(crate) async fn with_timeout<T>(
+    timeout: std::time::Duration,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
@@ -0,0 +1,67 @@
+use std::{fmt, io};
+
+use core::response::{ErrorCode, ResponseError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error returned by the client.
+#[derive(Debug)]
+pub enum Error {
+    /// Couldn't connect to the server, or the connection broke
+    Io(io::Error),
+    /// The server took longer than the configured timeout to answer
+    Timeout,
+    /// The server ran the command and answered with an error
+    Server(ResponseError),
+    /// The server answered with something the client didn't expect
+    Protocol(String),
+}
+
+impl Error {
+    /// Code of the error returned by the server, if it was one.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::Server(e) => Some(e.code),
+            _ => None,
+        }
+    }
+
+    /// Whether the connection the error happened on can't be used anymore,
+    /// so the request can be retried on a new one.
+    pub(crate) fn is_connection_error(&self) -> bool {
+        matches!(self, Error::Io(_) | Error::Timeout | Error::Protocol(_))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "connection error: {}", e),
+            Error::Timeout => f.write_str("timed out waiting for the server"),
+            Error::Server(e) => e.fmt(f),
+            Error::Protocol(message) => write!(f, "protocol error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Server(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ResponseError> for Error {
+    fn from(error: ResponseError) -> Self {
+        Error::Server(error)
+    }
+}
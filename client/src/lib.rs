@@ -0,0 +1,148 @@
+/*!
+* Client library for TunaDB.
+*
+* `Client` is async and cheap to clone: clones share a pool of connections
+* that are opened as needed, negotiate the protocol version and
+* authenticate when the options have credentials. Requests that fail
+* because their connection broke are retried on a new connection.
+* `blocking::Client` wraps it for code that doesn't run in a Tokio runtime.
+*
+* `Connection` is a single connection, for the commands that keep state on
+* the server: transactions, subscriptions and CDC.
+*/
+pub mod blocking;
+mod connection;
+mod error;
+mod options;
+mod pool;
+mod tls;
+
+use std::sync::Arc;
+
+use core::command::Command;
+use core::protocol::Hello;
+use core::response::{Response, Status};
+use core::serializer::{
+    CommandSerializer, ProtoCommandSerializer, ProtoResponseSerializer, ResponseSerializer,
+};
+
+pub use connection::Connection;
+use connection::{encode, into_result, with_timeout};
+pub use error::{Error, Result};
+pub use options::{Address, Options, TlsOptions};
+use pool::Pool;
+
+pub use core::response::{ErrorCode, ResponseError};
+
+#[derive(Clone)]
+pub struct Client {
+    pool: Arc<Pool>,
+}
+
+impl Client {
+    /// Creates a client speaking protobuf, checking the server can be reached.
+    pub async fn connect(options: Options) -> Result<Client> {
+        Client::with_serializers(
+            options,
+            Box::new(ProtoCommandSerializer),
+            Box::new(ProtoResponseSerializer),
+        )
+        .await
+    }
+
+    /// Creates a client encoding the messages with the given serializers,
+    /// checking the server can be reached.
+    pub async fn with_serializers(
+        options: Options,
+        command_serializer: Box<dyn CommandSerializer>,
+        response_serializer: Box<dyn ResponseSerializer>,
+    ) -> Result<Client> {
+        let client = Client {
+            pool: Arc::new(Pool::new(options, command_serializer, response_serializer)),
+        };
+        client.pool.get().await?;
+        Ok(client)
+    }
+
+    /// Returns the value of the key, `None` if it doesn't exist.
+    pub async fn get(&self, key: &str) -> Result<Option<String>> {
+        let response = self
+            .request(Command::Get {
+                key: key.to_string(),
+            })
+            .await?;
+        match response.status {
+            Status::NotFound => Ok(None),
+            _ => Ok(response.content),
+        }
+    }
+
+    pub async fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.request(Command::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn del(&self, key: &str) -> Result<()> {
+        self.request(Command::Del {
+            key: key.to_string(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns every key, in no particular order.
+    pub async fn list(&self) -> Result<Vec<String>> {
+        Ok(self.request(Command::List).await?.keys)
+    }
+
+    pub async fn ping(&self) -> Result<()> {
+        self.request(Command::Ping { message: None }).await?;
+        Ok(())
+    }
+
+    /// Reply of the server to the handshake, `None` if the server predates it.
+    pub async fn server_info(&self) -> Result<Option<Hello>> {
+        let mut pooled = self.pool.get().await?;
+        Ok(pooled.connection().hello().cloned())
+    }
+
+    /// Runs the command, retrying it on a new connection if its connection
+    /// fails. Error responses are returned as `Error::Server`.
+    async fn request(&self, command: Command) -> Result<Response> {
+        let options = &self.pool.options;
+        let command = encode(self.pool.command_serializer.as_ref(), &command)?;
+
+        let mut retries = 0;
+        let mut backoff = options.retry_backoff;
+        loop {
+            match self.try_request(&command).await {
+                Err(e) if e.is_connection_error() && retries < options.retries => {
+                    retries += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result.and_then(into_result),
+            }
+        }
+    }
+
+    async fn try_request(&self, command: &[u8]) -> Result<Response> {
+        let mut pooled = self.pool.get().await?;
+        let response_serializer = self.pool.response_serializer.as_ref();
+        let result = with_timeout(
+            self.pool.options.request_timeout,
+            pooled.connection().run(command, response_serializer),
+        )
+        .await;
+
+        // The state of the stream is unknown, it can't be reused
+        if matches!(&result, Err(e) if e.is_connection_error()) {
+            pooled.discard();
+        }
+        result
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Where the server listens.
+#[derive(Debug, Clone)]
+pub enum Address {
+    /// `host:port` of a TCP listener
+    Tcp(String),
+    /// Path of a Unix domain socket
+    Unix(PathBuf),
+}
+
+/// Certificates of TLS connections, which the server must accept on its
+/// TCP listeners.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// PEM file with the CA the server certificate is verified with. The
+    /// webpki roots are trusted when there's none
+    pub ca_cert: Option<PathBuf>,
+    /// PEM client certificate and its private key, for servers that require
+    /// mTLS. Both must be given
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+/// Settings of a client. Fields left out take the defaults:
+///
+///   let options = Options {
+///       pool_size: 4,
+///       ..Options::tcp("127.0.0.1:5880")
+///   };
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub address: Address,
+    /// Connects over TLS when set. Only used with TCP addresses
+    pub tls: Option<TlsOptions>,
+    /// Credentials connections authenticate with, if the server requires them
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Maximum number of connections open at the same time
+    pub pool_size: usize,
+    /// Time given to open a connection, handshake included
+    pub connect_timeout: Duration,
+    /// Time given to the server to answer a request
+    pub request_timeout: Duration,
+    /// Times a request is retried on a new connection when its connection
+    /// fails. Every command the client sends is safe to repeat
+    pub retries: usize,
+    /// Wait before the first retry, doubled on every following one
+    pub retry_backoff: Duration,
+    /// Size in bytes of the largest response accepted. Larger ones break the
+    /// connection instead of being read into memory
+    pub max_response_size: usize,
+}
+
+impl Options {
+    pub fn tcp(address: impl Into<String>) -> Self {
+        Options::new(Address::Tcp(address.into()))
+    }
+
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Options::new(Address::Unix(path.into()))
+    }
+
+    fn new(address: Address) -> Self {
+        Options {
+            address,
+            tls: None,
+            user: None,
+            password: None,
+            pool_size: 8,
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(30),
+            retries: 3,
+            retry_backoff: Duration::from_millis(100),
+            max_response_size: 512 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use core::serializer::{CommandSerializer, ResponseSerializer};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::connection::Connection;
+use crate::error::Result;
+use crate::options::Options;
+
+/**
+* Pool of connections shared by the clones of a client. Connections are
+* opened when needed, up to `pool_size`, and reused once they're returned.
+* Callers wait for a connection to be returned when they're all in use.
+*/
+pub(crate) struct Pool {
+    pub options: Options,
+    pub command_serializer: Box<dyn CommandSerializer>,
+    pub response_serializer: Box<dyn ResponseSerializer>,
+    idle: Mutex<Vec<Connection>>,
+    /// One permit per connection that can be open
+    permits: Arc<Semaphore>,
+}
+
+/// Connection taken from the pool. It goes back to the pool when dropped,
+/// unless it was discarded.
+pub(crate) struct PooledConnection<'a> {
+    pool: &'a Pool,
+    connection: Option<Connection>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Pool {
+    pub fn new(
+        options: Options,
+        command_serializer: Box<dyn CommandSerializer>,
+        response_serializer: Box<dyn ResponseSerializer>,
+    ) -> Self {
+        Pool {
+            permits: Arc::new(Semaphore::new(options.pool_size.max(1))),
+            options,
+            command_serializer,
+            response_serializer,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Takes an idle connection, or opens a new one.
+    pub async fn get(&self) -> Result<PooledConnection<'_>> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the pool's semaphore is never closed");
+
+        let idle = self.idle.lock().unwrap().pop();
+        let connection = match idle {
+            Some(connection) => connection,
+            None => {
+                Connection::open(
+                    &self.options,
+                    self.command_serializer.as_ref(),
+                    self.response_serializer.as_ref(),
+                )
+                .await?
+            }
+        };
+        Ok(PooledConnection {
+            pool: self,
+            connection: Some(connection),
+            _permit: permit,
+        })
+    }
+}
+
+impl PooledConnection<'_> {
+    pub fn connection(&mut self) -> &mut Connection {
+        self.connection
+            .as_mut()
+            .expect("connections are only taken when dropped or discarded")
+    }
+
+    /// Closes the connection instead of returning it to the pool, after it
+    /// failed. The idle ones are closed too, as they're likely broken for
+    /// the same reason (such as the server restarting).
+    pub fn discard(mut self) {
+        self.connection = None;
+        self.pool.idle.lock().unwrap().clear();
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.idle.lock().unwrap().push(connection);
+        }
+    }
+}
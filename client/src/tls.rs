@@ -0,0 +1,134 @@
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::{fs::File, sync::Arc};
+
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, RootCertStore,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::options::TlsOptions;
+
+/// Runs the TLS handshake over the socket, checking the server certificate
+/// is valid for the host of `address`.
+pub(crate) async fn connect(
+    options: &TlsOptions,
+    address: &str,
+    socket: TcpStream,
+) -> io::Result<TlsStream<TcpStream>> {
+    let connector = TlsConnector::from(Arc::new(config(options)?));
+    connector.connect(server_name(address)?, socket).await
+}
+
+/// Trusts the CA of the options, or the webpki roots when there's none, and
+/// presents the client certificate if there's one.
+fn config(options: &TlsOptions) -> io::Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    match &options.ca_cert {
+        Some(ca_cert) => {
+            for cert in load_certs(ca_cert)? {
+                roots.add(cert).map_err(invalid)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    match (&options.cert, &options.key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_private_key(key)?)
+            .map_err(invalid),
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(invalid(
+            "a client certificate needs both a certificate and a key",
+        )),
+    }
+}
+
+/// Name the server certificate must be valid for: the host of a `host:port`
+/// address, in which IPv6 addresses must be in brackets (`[::1]:5880`).
+fn server_name(address: &str) -> io::Result<ServerName<'static>> {
+    if let Ok(address) = address.parse::<SocketAddr>() {
+        return Ok(ServerName::from(address.ip()));
+    }
+    let invalid_address = || invalid(format!("invalid address '{}', expected host:port", address));
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid_address)?;
+    // IP addresses were parsed above, so these are unbracketed IPv6 addresses
+    if host.contains([':', '[', ']']) || port.parse::<u16>().is_err() {
+        return Err(invalid_address());
+    }
+    ServerName::try_from(host.to_string()).map_err(invalid)
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("couldn't open certificate {}: {}", path.display(), e),
+        )
+    })?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "no certificates found in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("couldn't open private key {}: {}", path.display(), e),
+        )
+    })?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| invalid(format!("no private key found in {}", path.display())))
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(address: &str) -> Option<String> {
+        server_name(address)
+            .ok()
+            .map(|name| name.to_str().into_owned())
+    }
+
+    #[test]
+    fn server_name_is_the_host_of_the_address() {
+        assert_eq!(name("localhost:5880").as_deref(), Some("localhost"));
+        assert_eq!(
+            name("db.example.com:5880").as_deref(),
+            Some("db.example.com")
+        );
+        assert_eq!(name("127.0.0.1:5880").as_deref(), Some("127.0.0.1"));
+        assert_eq!(name("[::1]:5880").as_deref(), Some("::1"));
+    }
+
+    #[test]
+    fn server_name_rejects_addresses_without_a_port() {
+        assert_eq!(name("localhost"), None);
+        assert_eq!(name("localhost:"), None);
+        assert_eq!(name("localhost:port"), None);
+        assert_eq!(name("[::1]"), None);
+    }
+
+    #[test]
+    fn server_name_rejects_unbracketed_ipv6_addresses() {
+        assert_eq!(name("::1"), None);
+        assert_eq!(name("::1:5880"), None);
+        assert_eq!(name("fe80::1"), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! Runs the client against a fake server, which records the commands it
+//! receives and answers them without a database.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use client::{Client, Connection, Error, ErrorCode, Options, ResponseError};
+use core::command::Command;
+use core::frame;
+use core::protocol::Hello;
+use core::response::{Response, Status};
+use core::serializer::{
+    CommandSerializer, ProtoCommandSerializer, ProtoResponseSerializer, ResponseSerializer,
+};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Answers a command received on the connection with the given number
+/// (from 0). Returning `None` drops the connection instead, like a server
+/// that crashed.
+type Answer = dyn Fn(usize, &Command) -> Option<Response> + Send + Sync;
+
+struct FakeServer {
+    address: String,
+    /// Commands received, with the number of their connection
+    received: Arc<Mutex<Vec<(usize, String)>>>,
+}
+
+impl FakeServer {
+    async fn start(
+        answer: impl Fn(usize, &Command) -> Option<Response> + Send + Sync + 'static,
+    ) -> FakeServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let answer: Arc<Answer> = Arc::new(answer);
+
+        let connections = AtomicUsize::new(0);
+        let log = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let number = connections.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(serve(socket, number, answer.clone(), log.clone()));
+            }
+        });
+        FakeServer { address, received }
+    }
+
+    fn options(&self) -> Options {
+        Options::tcp(&self.address)
+    }
+
+    fn received(&self) -> Vec<(usize, String)> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Number of connections the commands came from.
+    fn connections(&self) -> usize {
+        let mut connections: Vec<usize> = self.received().iter().map(|(n, _)| *n).collect();
+        connections.sort();
+        connections.dedup();
+        connections.len()
+    }
+}
+
+async fn serve(
+    mut socket: TcpStream,
+    number: usize,
+    answer: Arc<Answer>,
+    received: Arc<Mutex<Vec<(usize, String)>>>,
+) {
+    while let Ok(Some(bytes)) = frame::read_frame(&mut socket, 1024 * 1024).await {
+        let command = ProtoCommandSerializer.decode(&bytes).unwrap();
+        received.lock().unwrap().push((number, command.name()));
+        // Slow enough for requests sent at the same time to overlap
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let Some(response) = answer(number, &command) else {
+            return;
+        };
+        let mut buf = Vec::new();
+        ProtoResponseSerializer.encode(&response, &mut buf).unwrap();
+        frame::write_frame(&mut socket, &buf).await.unwrap();
+    }
+}
+
+/// Answers like a server with the user `admin`, whose password is `secret`.
+fn server(_: usize, command: &Command) -> Option<Response> {
+    Some(match command {
+        Command::Hello { .. } => Response::hello(Hello {
+            protocol_version: 1,
+            server_version: "test".to_string(),
+            features: vec!["auth".to_string()],
+        }),
+        Command::Auth { user, password } if user == "admin" && password.0 == "secret" => {
+            Response::ok(None)
+        }
+        Command::Auth { .. } => Response::error(ResponseError::new(
+            ErrorCode::Unauthorized,
+            "invalid username or password",
+        )),
+        Command::Get { .. } => Response::ok(Some("value".to_string())),
+        _ => Response::ok(None),
+    })
+}
+
+fn commands(received: &[(usize, String)]) -> Vec<&str> {
+    received.iter().map(|(_, name)| name.as_str()).collect()
+}
+
+/// Runs a test on a new runtime. `#[tokio::test]` can't be used, as the
+/// `core` crate shadows the one its expansion refers to.
+fn run(test: impl std::future::Future<Output = ()>) {
+    tokio::runtime::Runtime::new().unwrap().block_on(test)
+}
+
+#[test]
+fn connections_say_hello_then_authenticate() {
+    run(async {
+        let server = FakeServer::start(server).await;
+        let client = Client::connect(Options {
+            user: Some("admin".to_string()),
+            password: Some("secret".to_string()),
+            ..server.options()
+        })
+        .await
+        .unwrap();
+
+        client.ping().await.unwrap();
+        assert_eq!(commands(&server.received()), ["hello", "auth", "ping"]);
+        let hello = client.server_info().await.unwrap().unwrap();
+        assert_eq!(hello.server_version, "test");
+    });
+}
+
+#[test]
+fn connections_skip_auth_without_credentials() {
+    run(async {
+        let server = FakeServer::start(server).await;
+        let client = Client::connect(server.options()).await.unwrap();
+
+        client.ping().await.unwrap();
+        assert_eq!(commands(&server.received()), ["hello", "ping"]);
+    });
+}
+
+#[test]
+fn connect_fails_with_invalid_credentials() {
+    run(async {
+        let server = FakeServer::start(server).await;
+        let result = Client::connect(Options {
+            user: Some("admin".to_string()),
+            password: Some("wrong".to_string()),
+            ..server.options()
+        })
+        .await;
+
+        assert_eq!(result.err().unwrap().code(), Some(ErrorCode::Unauthorized));
+    });
+}
+
+#[test]
+fn connect_fails_with_an_incompatible_protocol() {
+    run(async {
+        let server = FakeServer::start(|_, command| match command {
+            Command::Hello { .. } => Some(Response::error(ResponseError::new(
+                ErrorCode::IncompatibleProtocol,
+                "unsupported protocol version",
+            ))),
+            _ => Some(Response::ok(None)),
+        })
+        .await;
+
+        let result = Client::connect(server.options()).await;
+        assert_eq!(
+            result.err().unwrap().code(),
+            Some(ErrorCode::IncompatibleProtocol)
+        );
+        assert_eq!(commands(&server.received()), ["hello"]);
+    });
+}
+
+#[test]
+fn connections_are_reused() {
+    run(async {
+        let server = FakeServer::start(server).await;
+        let client = Client::connect(server.options()).await.unwrap();
+
+        for _ in 0..5 {
+            client.get("key").await.unwrap();
+        }
+        assert_eq!(server.connections(), 1);
+    });
+}
+
+#[test]
+fn concurrent_requests_share_at_most_pool_size_connections() {
+    run(async {
+        let server = FakeServer::start(server).await;
+        let client = Client::connect(Options {
+            pool_size: 2,
+            ..server.options()
+        })
+        .await
+        .unwrap();
+
+        let requests: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move { client.get("key").await })
+            })
+            .collect();
+        for request in requests {
+            assert_eq!(request.await.unwrap().unwrap().as_deref(), Some("value"));
+        }
+
+        let received = server.received();
+        assert_eq!(
+            commands(&received).iter().filter(|c| **c == "get").count(),
+            10
+        );
+        assert_eq!(
+            commands(&received)
+                .iter()
+                .filter(|c| **c == "hello")
+                .count(),
+            2
+        );
+    });
+}
+
+#[test]
+fn requests_are_retried_on_a_new_connection() {
+    run(async {
+        // The first connection breaks on the first request
+        let server = FakeServer::start(|connection, command| match command {
+            Command::Ping { .. } if connection == 0 => None,
+            command => server(connection, command),
+        })
+        .await;
+        let client = Client::connect(server.options()).await.unwrap();
+
+        client.ping().await.unwrap();
+        assert_eq!(
+            server.received(),
+            [
+                (0, "hello".to_string()),
+                (0, "ping".to_string()),
+                (1, "hello".to_string()),
+                (1, "ping".to_string()),
+            ]
+        );
+    });
+}
+
+#[test]
+fn retries_back_off_and_give_up() {
+    run(async {
+        let server = FakeServer::start(|connection, command| match command {
+            Command::Ping { .. } => None,
+            command => server(connection, command),
+        })
+        .await;
+        let client = Client::connect(Options {
+            retries: 3,
+            retry_backoff: Duration::from_millis(50),
+            ..server.options()
+        })
+        .await
+        .unwrap();
+
+        let started = Instant::now();
+        let result = client.ping().await;
+        assert!(matches!(result, Err(Error::Io(_))));
+        // 50ms, then 100ms and 200ms
+        assert!(started.elapsed() >= Duration::from_millis(350));
+        let pings = commands(&server.received())
+            .iter()
+            .filter(|c| **c == "ping")
+            .count();
+        assert_eq!(pings, 4);
+    });
+}
+
+#[test]
+fn server_errors_are_not_retried() {
+    run(async {
+        let server = FakeServer::start(|connection, command| match command {
+            Command::Ping { .. } => Some(Response::error(ResponseError::new(
+                ErrorCode::Internal,
+                "broken",
+            ))),
+            command => server(connection, command),
+        })
+        .await;
+        let client = Client::connect(server.options()).await.unwrap();
+
+        let result = client.ping().await;
+        assert_eq!(result.err().unwrap().code(), Some(ErrorCode::Internal));
+        assert_eq!(commands(&server.received()), ["hello", "ping"]);
+    });
+}
+
+#[test]
+fn connection_returns_error_responses() {
+    run(async {
+        let server = FakeServer::start(|connection, command| match command {
+            Command::Get { .. } => Some(Response::not_found()),
+            command => server(connection, command),
+        })
+        .await;
+        let mut connection = Connection::connect(&server.options()).await.unwrap();
+        assert_eq!(connection.hello().unwrap().server_version, "test");
+
+        let get = Command::Get {
+            key: "key".to_string(),
+        };
+        let response = connection.request(&get).await.unwrap();
+        assert!(matches!(response.status, Status::NotFound));
+        assert_eq!(commands(&server.received()), ["hello", "get"]);
+    });
+}
+
+#[test]
+fn responses_over_the_max_size_are_rejected() {
+    run(async {
+        let server = FakeServer::start(|connection, command| match command {
+            Command::Get { .. } => Some(Response::ok(Some("v".repeat(2000)))),
+            command => server(connection, command),
+        })
+        .await;
+        let mut connection = Connection::connect(&Options {
+            max_response_size: 1000,
+            ..server.options()
+        })
+        .await
+        .unwrap();
+
+        let get = Command::Get {
+            key: "key".to_string(),
+        };
+        let result = connection.request(&get).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData));
+    });
+}
@@ -1,4 +1,3 @@
-use prost_build;
 use std::{fs::read_dir, io::Result};
 
 const PROTO_FILES_DIR: &str = "./src/protobuf/protofiles";
@@ -7,7 +6,6 @@ const PROTO_OUT_DIR: &str = "./src/protobuf/";
 fn main() -> Result<()> {
     let proto_files: Vec<String> = read_dir(PROTO_FILES_DIR)
         .expect("Failed to read protofiles directory")
-        .into_iter()
         .filter_map(|entry| {
             entry.ok().and_then(|e| {
                 e.path()
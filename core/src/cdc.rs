@@ -0,0 +1,70 @@
+use tokio::sync::watch;
+
+use crate::command::Context;
+use crate::response::ResponseError;
+use crate::storage::LogRecord;
+
+/// Maximum records read from the log at once, so followers catching up
+/// don't hold the engine for too long.
+pub const BATCH_SIZE: usize = 1024;
+
+/**
+* Change data capture: streams the records of the log from a given position
+* and then follows new writes as they happen.
+*
+* Positions are the sequence numbers of the records, so a follower resumes
+* without gaps or duplicates by starting from the last sequence it saw.
+*/
+pub struct ChangeStream {
+    ctx: Context,
+    writes: watch::Receiver<u64>,
+    position: u64,
+}
+
+impl ChangeStream {
+    /// Starts streaming from the position, failing if it's past the end of
+    /// the log or in the middle of a record.
+    pub async fn open(ctx: &Context, position: u64) -> Result<Self, ResponseError> {
+        ctx.engine
+            .lock()
+            .await
+            .check_log_position(position)
+            .await
+            .map_err(|e| ResponseError::from(&e))?;
+        Ok(ChangeStream {
+            ctx: ctx.clone(),
+            writes: ctx.writes.subscribe(),
+            position,
+        })
+    }
+
+    /// Position the next batch will be read from.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the next batch of records, waiting for new writes once the
+    /// end of the log is reached. It's cancel safe.
+    pub async fn next_batch(&mut self) -> Result<Vec<LogRecord>, ResponseError> {
+        loop {
+            // Marked as seen before reading, so writes made meanwhile aren't missed
+            self.writes.borrow_and_update();
+            let records = self
+                .ctx
+                .engine
+                .lock()
+                .await
+                .read_log(self.position, BATCH_SIZE)
+                .await
+                .map_err(|e| ResponseError::from(&e))?;
+
+            if let Some(last) = records.last() {
+                self.position = last.sequence;
+                return Ok(records);
+            }
+
+            // The sender lives in the context held by the stream, so it can't be closed
+            let _ = self.writes.changed().await;
+        }
+    }
+}
@@ -1,11 +1,33 @@
-use crate::response::{Response, Status};
+use crate::response::{ErrorCode, Response, ResponseError};
 use crate::{
+    info::{self, Info},
     proto::{self, command::Operation},
-    storage::Engine,
+    protocol::{self, Hello},
+    pubsub::{Broker, KEYSPACE_CHANNEL_PREFIX},
+    storage::{self, Engine},
+    wait::Waiters,
 };
 use anyhow::anyhow;
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{watch, Mutex};
+
+/// Number of entries SLOWLOG GET returns when the client doesn't say.
+pub const DEFAULT_SLOWLOG_COUNT: u32 = 10;
+
+/// String that is kept out of the logs, such as a password.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
 
 #[derive(Debug)]
 pub enum Command {
@@ -17,51 +39,380 @@ pub enum Command {
     Del { key: String },
     /// Lists all keys in the database
     List,
+    /// Authenticates the connection as the specified user
+    Auth { user: String, password: Secret },
+    /// Checks the server is alive, echoing the message if there's one
+    Ping { message: Option<String> },
+    /// Subscribes the connection to the specified channels
+    Subscribe { channels: Vec<String> },
+    /// Unsubscribes the connection from the specified channels, or from all of them
+    Unsubscribe { channels: Vec<String> },
+    /// Subscribes the connection to the channels matching the specified patterns
+    Psubscribe { patterns: Vec<String> },
+    /// Unsubscribes the connection from the specified patterns, or from all of them
+    Punsubscribe { patterns: Vec<String> },
+    /// Sends a message to the subscribers of the specified channel
+    Publish { channel: String, message: String },
+    /// Streams the changes in the log from the specified position, and then
+    /// follows new writes
+    Cdc { position: u64 },
+    /// Waits until the specified key exists, or until it's written again when
+    /// `changed` is set, and returns its value. Gives up after `timeout_ms`
+    /// milliseconds, unless it's 0
+    Wait {
+        key: String,
+        timeout_ms: u64,
+        changed: bool,
+    },
+    /// Starts queuing commands, to run them as a unit with EXEC
+    Multi,
+    /// Runs the queued commands, unless a watched key changed
+    Exec,
+    /// Drops the queued commands
+    Discard,
+    /// Aborts the next EXEC if any of the specified keys changes before it
+    Watch { keys: Vec<String> },
+    /// Stops watching every key
+    Unwatch,
+    /// Negotiates the protocol version, announcing the latest one the client
+    /// speaks and its features
+    Hello { version: u32, features: Vec<String> },
+    /// Describes the server and the database
+    Info,
+    /// Returns the number of keys in the database
+    Dbsize,
+    /// Deletes every key in the database
+    Flushdb,
+    /// Returns the settings whose name matches the specified pattern
+    ConfigGet { pattern: String },
+    /// Changes a setting of the running server
+    ConfigSet { parameter: String, value: String },
+    /// Writes the current settings to the config file
+    ConfigRewrite,
+    /// Returns up to `count` of the latest slow commands, newest first
+    SlowlogGet { count: u32 },
+    /// Empties the slow log
+    SlowlogReset,
+    /// Returns the number of commands in the slow log
+    SlowlogLen,
 }
 
 impl Command {
+    pub fn operation(&self) -> Operation {
+        match self {
+            Command::Get { .. } => Operation::Get,
+            Command::Set { .. } => Operation::Set,
+            Command::Del { .. } => Operation::Del,
+            Command::List => Operation::List,
+            Command::Auth { .. } => Operation::Auth,
+            Command::Ping { .. } => Operation::Ping,
+            Command::Subscribe { .. } => Operation::Subscribe,
+            Command::Unsubscribe { .. } => Operation::Unsubscribe,
+            Command::Psubscribe { .. } => Operation::Psubscribe,
+            Command::Punsubscribe { .. } => Operation::Punsubscribe,
+            Command::Publish { .. } => Operation::Publish,
+            Command::Cdc { .. } => Operation::Cdc,
+            Command::Wait { .. } => Operation::Wait,
+            Command::Multi => Operation::Multi,
+            Command::Exec => Operation::Exec,
+            Command::Discard => Operation::Discard,
+            Command::Watch { .. } => Operation::Watch,
+            Command::Unwatch => Operation::Unwatch,
+            Command::Hello { .. } => Operation::Hello,
+            Command::Info => Operation::Info,
+            Command::Dbsize => Operation::Dbsize,
+            Command::Flushdb => Operation::Flushdb,
+            Command::ConfigGet { .. } => Operation::ConfigGet,
+            Command::ConfigSet { .. } => Operation::ConfigSet,
+            Command::ConfigRewrite => Operation::ConfigRewrite,
+            Command::SlowlogGet { .. } => Operation::SlowlogGet,
+            Command::SlowlogReset => Operation::SlowlogReset,
+            Command::SlowlogLen => Operation::SlowlogLen,
+        }
+    }
+
+    /// Lowercase name of the command, as typed in the CLI
+    pub fn name(&self) -> String {
+        self.operation().as_str_name().to_lowercase()
+    }
+
+    /// Keys the command reads or writes
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Command::Get { key }
+            | Command::Set { key, .. }
+            | Command::Del { key }
+            | Command::Wait { key, .. } => vec![key],
+            Command::Watch { keys } => keys.iter().map(String::as_str).collect(),
+            // Channels aren't keys, pub/sub commands are only restricted by name
+            Command::List
+            | Command::Auth { .. }
+            | Command::Ping { .. }
+            | Command::Subscribe { .. }
+            | Command::Unsubscribe { .. }
+            | Command::Psubscribe { .. }
+            | Command::Punsubscribe { .. }
+            | Command::Publish { .. }
+            | Command::Cdc { .. }
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Unwatch
+            | Command::Hello { .. }
+            | Command::Info
+            | Command::Dbsize
+            | Command::Flushdb
+            | Command::ConfigGet { .. }
+            | Command::ConfigSet { .. }
+            | Command::ConfigRewrite
+            | Command::SlowlogGet { .. }
+            | Command::SlowlogReset
+            | Command::SlowlogLen => vec![],
+        }
+    }
+
     pub fn to_proto_command(&self) -> proto::Command {
+        let mut proto_command = proto::Command {
+            operation: self.operation() as i32,
+            ..Default::default()
+        };
         match self {
-            Command::Get { key } => proto::Command {
-                key: key.to_string(),
-                operation: Operation::Get as i32,
-                value: None,
-            },
-            Command::Set { key, value } => proto::Command {
-                key: key.to_string(),
-                operation: Operation::Set as i32,
-                value: Some(value.to_string()),
-            },
-            Command::Del { key } => proto::Command {
-                key: key.to_string(),
-                operation: Operation::Del as i32,
-                value: None,
-            },
-            Command::List => proto::Command {
-                key: "".to_string(),
-                operation: Operation::List as i32,
-                value: None,
-            },
+            Command::Get { key } | Command::Del { key } => {
+                proto_command.key = key.to_string();
+            }
+            Command::Set { key, value } => {
+                proto_command.key = key.to_string();
+                proto_command.value = Some(value.to_string());
+            }
+            Command::List
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Unwatch
+            | Command::Info
+            | Command::Dbsize
+            | Command::Flushdb
+            | Command::ConfigRewrite
+            | Command::SlowlogReset
+            | Command::SlowlogLen => {}
+            Command::SlowlogGet { count } => {
+                proto_command.count = Some(*count);
+            }
+            Command::ConfigGet { pattern } => {
+                proto_command.key = pattern.to_string();
+            }
+            Command::ConfigSet { parameter, value } => {
+                proto_command.key = parameter.to_string();
+                proto_command.value = Some(value.to_string());
+            }
+            Command::Auth { user, password } => {
+                proto_command.key = user.to_string();
+                proto_command.value = Some(password.0.to_string());
+            }
+            Command::Ping { message } => {
+                proto_command.value = message.clone();
+            }
+            Command::Subscribe { channels } | Command::Unsubscribe { channels } => {
+                proto_command.args = channels.clone();
+            }
+            Command::Psubscribe { patterns } | Command::Punsubscribe { patterns } => {
+                proto_command.args = patterns.clone();
+            }
+            Command::Watch { keys } => {
+                proto_command.args = keys.clone();
+            }
+            Command::Hello { version, features } => {
+                proto_command.protocol_version = Some(*version);
+                proto_command.args = features.clone();
+            }
+            Command::Publish { channel, message } => {
+                proto_command.key = channel.to_string();
+                proto_command.value = Some(message.to_string());
+            }
+            Command::Cdc { position } => {
+                proto_command.position = Some(*position);
+            }
+            Command::Wait {
+                key,
+                timeout_ms,
+                changed,
+            } => {
+                proto_command.key = key.to_string();
+                proto_command.timeout_ms = Some(*timeout_ms);
+                proto_command.changed = *changed;
+            }
         }
+        proto_command
     }
 
-    pub fn from_proto_command(cmd: proto::Command) -> Command {
-        match cmd.operation() {
-            Operation::Get => Command::Get {
+    pub fn from_proto_command(cmd: proto::Command) -> Result<Command, ResponseError> {
+        let operation = Operation::try_from(cmd.operation).map_err(|_| {
+            ResponseError::new(
+                ErrorCode::Unsupported,
+                format!("unsupported operation ({})", cmd.operation),
+            )
+        })?;
+        match operation {
+            Operation::Get => Ok(Command::Get {
                 key: cmd.key.to_string(),
-            },
-            Operation::Set => Command::Set {
+            }),
+            Operation::Set => Ok(Command::Set {
                 key: cmd.key.to_string(),
                 value: cmd.value().to_string(),
-            },
-            Operation::Del => Command::Del {
+            }),
+            Operation::Del => Ok(Command::Del {
                 key: cmd.key.to_string(),
-            },
-            Operation::List => Command::List,
+            }),
+            Operation::List => Ok(Command::List),
+            Operation::Auth => Ok(Command::Auth {
+                user: cmd.key.to_string(),
+                password: Secret(cmd.value().to_string()),
+            }),
+            Operation::Ping => Ok(Command::Ping { message: cmd.value }),
+            Operation::Subscribe => Ok(Command::Subscribe { channels: cmd.args }),
+            Operation::Unsubscribe => Ok(Command::Unsubscribe { channels: cmd.args }),
+            Operation::Psubscribe => Ok(Command::Psubscribe { patterns: cmd.args }),
+            Operation::Punsubscribe => Ok(Command::Punsubscribe { patterns: cmd.args }),
+            Operation::Publish => Ok(Command::Publish {
+                channel: cmd.key.to_string(),
+                message: cmd.value().to_string(),
+            }),
+            Operation::Cdc => Ok(Command::Cdc {
+                position: cmd.position(),
+            }),
+            Operation::Wait => Ok(Command::Wait {
+                timeout_ms: cmd.timeout_ms(),
+                key: cmd.key,
+                changed: cmd.changed,
+            }),
+            Operation::Multi => Ok(Command::Multi),
+            Operation::Exec => Ok(Command::Exec),
+            Operation::Discard => Ok(Command::Discard),
+            Operation::Watch => Ok(Command::Watch { keys: cmd.args }),
+            Operation::Unwatch => Ok(Command::Unwatch),
+            Operation::Hello => Ok(Command::Hello {
+                version: cmd.protocol_version(),
+                features: cmd.args,
+            }),
+            Operation::Info => Ok(Command::Info),
+            Operation::Dbsize => Ok(Command::Dbsize),
+            Operation::Flushdb => Ok(Command::Flushdb),
+            Operation::ConfigGet => Ok(Command::ConfigGet {
+                pattern: cmd.key.to_string(),
+            }),
+            Operation::ConfigSet => Ok(Command::ConfigSet {
+                parameter: cmd.key.to_string(),
+                value: cmd.value().to_string(),
+            }),
+            Operation::ConfigRewrite => Ok(Command::ConfigRewrite),
+            Operation::SlowlogGet => Ok(Command::SlowlogGet {
+                count: cmd.count.unwrap_or(DEFAULT_SLOWLOG_COUNT),
+            }),
+            Operation::SlowlogReset => Ok(Command::SlowlogReset),
+            Operation::SlowlogLen => Ok(Command::SlowlogLen),
+        }
+    }
+
+    /// Checks the command arguments against the limits of the storage format.
+    pub fn validate(&self) -> Result<(), ResponseError> {
+        match self {
+            Command::Get { key } | Command::Del { key } | Command::Wait { key, .. } => {
+                validate_key(key)
+            }
+            Command::Set { key, value } => {
+                validate_key(key)?;
+                validate_value(value)
+            }
+            Command::Subscribe { channels } | Command::Psubscribe { patterns: channels } => {
+                if channels.is_empty() || channels.iter().any(|c| c.is_empty()) {
+                    return Err(ResponseError::new(
+                        ErrorCode::InvalidArgument,
+                        "at least one non-empty channel is required",
+                    ));
+                }
+                Ok(())
+            }
+            Command::Publish { channel, .. } if channel.is_empty() => Err(ResponseError::new(
+                ErrorCode::InvalidArgument,
+                "channel can't be empty",
+            )),
+            Command::Publish { channel, .. } if channel.starts_with(KEYSPACE_CHANNEL_PREFIX) => {
+                Err(ResponseError::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "channels starting with '{}' are reserved for keyspace notifications",
+                        KEYSPACE_CHANNEL_PREFIX
+                    ),
+                ))
+            }
+            Command::Watch { keys } => {
+                if keys.is_empty() {
+                    return Err(ResponseError::new(
+                        ErrorCode::InvalidArgument,
+                        "at least one key is required",
+                    ));
+                }
+                keys.iter().try_for_each(|key| validate_key(key))
+            }
+            Command::List
+            | Command::Auth { .. }
+            | Command::Ping { .. }
+            | Command::Unsubscribe { .. }
+            | Command::Punsubscribe { .. }
+            | Command::Publish { .. }
+            | Command::Cdc { .. }
+            | Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Unwatch
+            | Command::Hello { .. }
+            | Command::Info
+            | Command::Dbsize
+            | Command::Flushdb
+            | Command::ConfigGet { .. }
+            | Command::ConfigSet { .. }
+            | Command::ConfigRewrite
+            | Command::SlowlogGet { .. }
+            | Command::SlowlogReset
+            | Command::SlowlogLen => Ok(()),
         }
     }
 }
 
+pub(crate) fn validate_key(key: &str) -> Result<(), ResponseError> {
+    if key.is_empty() {
+        return Err(ResponseError::new(
+            ErrorCode::InvalidArgument,
+            "key can't be empty",
+        ));
+    }
+    if key.len() > storage::MAX_KEY_SIZE {
+        return Err(ResponseError::new(
+            ErrorCode::KeyTooLarge,
+            format!(
+                "key is {} bytes long, the maximum is {}",
+                key.len(),
+                storage::MAX_KEY_SIZE
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_value(value: &str) -> Result<(), ResponseError> {
+    if value.len() > storage::MAX_VALUE_SIZE {
+        return Err(ResponseError::new(
+            ErrorCode::ValueTooLarge,
+            format!(
+                "value is {} bytes long, the maximum is {}",
+                value.len(),
+                storage::MAX_VALUE_SIZE
+            ),
+        ));
+    }
+    Ok(())
+}
+
 impl FromStr for Command {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -91,6 +442,110 @@ impl FromStr for Command {
                     .to_string(),
             }),
             "list" => Ok(Command::List),
+            "auth" => Ok(Command::Auth {
+                user: tokens
+                    .next()
+                    .ok_or(anyhow!("User not found in auth command"))?
+                    .to_string(),
+                password: Secret(
+                    tokens
+                        .next()
+                        .ok_or(anyhow!("Password not found in auth command"))?
+                        .to_string(),
+                ),
+            }),
+            "ping" => Ok(Command::Ping {
+                message: tokens.next().map(|message| message.to_string()),
+            }),
+            "subscribe" => Ok(Command::Subscribe {
+                channels: tokens.map(|channel| channel.to_string()).collect(),
+            }),
+            "unsubscribe" => Ok(Command::Unsubscribe {
+                channels: tokens.map(|channel| channel.to_string()).collect(),
+            }),
+            "psubscribe" => Ok(Command::Psubscribe {
+                patterns: tokens.map(|pattern| pattern.to_string()).collect(),
+            }),
+            "punsubscribe" => Ok(Command::Punsubscribe {
+                patterns: tokens.map(|pattern| pattern.to_string()).collect(),
+            }),
+            "publish" => Ok(Command::Publish {
+                channel: tokens
+                    .next()
+                    .ok_or(anyhow!("Channel not found in publish command"))?
+                    .to_string(),
+                message: tokens.collect::<Vec<_>>().join(" "),
+            }),
+            "cdc" => Ok(Command::Cdc {
+                position: match tokens.next() {
+                    Some(position) => position.parse()?,
+                    None => 0,
+                },
+            }),
+            "wait" => Ok(Command::Wait {
+                key: tokens
+                    .next()
+                    .ok_or(anyhow!("Key not found in wait command"))?
+                    .to_string(),
+                timeout_ms: match tokens.next() {
+                    Some(timeout_ms) => timeout_ms.parse()?,
+                    None => 0,
+                },
+                changed: match tokens.next() {
+                    Some("changed") => true,
+                    Some(_) => return Err(anyhow!("Unknown wait option, expected 'changed'")),
+                    None => false,
+                },
+            }),
+            "multi" => Ok(Command::Multi),
+            "exec" => Ok(Command::Exec),
+            "discard" => Ok(Command::Discard),
+            "watch" => Ok(Command::Watch {
+                keys: tokens.map(|key| key.to_string()).collect(),
+            }),
+            "unwatch" => Ok(Command::Unwatch),
+            "hello" => Ok(Command::Hello {
+                version: match tokens.next() {
+                    Some(version) => version.parse()?,
+                    None => protocol::PROTOCOL_VERSION,
+                },
+                features: tokens.map(|feature| feature.to_string()).collect(),
+            }),
+            "info" => Ok(Command::Info),
+            "dbsize" => Ok(Command::Dbsize),
+            "flushdb" => Ok(Command::Flushdb),
+            "config" => match tokens.next() {
+                Some("get") => Ok(Command::ConfigGet {
+                    pattern: tokens.next().unwrap_or("*").to_string(),
+                }),
+                Some("set") => Ok(Command::ConfigSet {
+                    parameter: tokens
+                        .next()
+                        .ok_or(anyhow!("Parameter not found in config set command"))?
+                        .to_string(),
+                    value: tokens
+                        .next()
+                        .ok_or(anyhow!("Value not found in config set command"))?
+                        .to_string(),
+                }),
+                Some("rewrite") => Ok(Command::ConfigRewrite),
+                _ => Err(anyhow!(
+                    "Unknown config command, expected get, set or rewrite"
+                )),
+            },
+            "slowlog" => match tokens.next() {
+                Some("get") => Ok(Command::SlowlogGet {
+                    count: match tokens.next() {
+                        Some(count) => count.parse()?,
+                        None => DEFAULT_SLOWLOG_COUNT,
+                    },
+                }),
+                Some("reset") => Ok(Command::SlowlogReset),
+                Some("len") => Ok(Command::SlowlogLen),
+                _ => Err(anyhow!(
+                    "Unknown slowlog command, expected get, reset or len"
+                )),
+            },
             _ => Err(anyhow!("Unknown command")),
         }
     }
@@ -120,7 +575,7 @@ pub async fn run(engine: Arc<Mutex<Box<dyn Engine>>>, command: Command) -> anyho
                     for key in keys {
                         result.push_str(&format!("- {}\n", key));
                     }
-                    result.push_str("\n");
+                    result.push('\n');
                 }
                 Err(e) => {
                     result.push_str(&e.to_string());
@@ -128,64 +583,230 @@ pub async fn run(engine: Arc<Mutex<Box<dyn Engine>>>, command: Command) -> anyho
             }
             Ok(result)
         }
+        _ => Err(anyhow!("Unsupported command")),
+    }
+}
+
+/// State commands are run against, shared by every connection.
+#[derive(Clone)]
+pub struct Context {
+    pub engine: Arc<Mutex<Box<dyn Engine>>>,
+    pub broker: Broker,
+    /// Publishes a keyspace notification whenever a key changes
+    pub keyspace_notifications: bool,
+    /// Number of writes, watched by CDC followers waiting for new records
+    pub(crate) writes: Arc<watch::Sender<u64>>,
+    /// Clients blocked in WAIT or watching keys
+    pub(crate) waiters: Waiters,
+    /// When the context was created, reported as the uptime by INFO
+    started: Instant,
+}
+
+impl Context {
+    pub fn new(engine: Box<dyn Engine>) -> Self {
+        Context {
+            engine: Arc::new(Mutex::new(engine)),
+            broker: Broker::new(),
+            keyspace_notifications: false,
+            writes: Arc::new(watch::Sender::new(0)),
+            waiters: Waiters::default(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Called while still holding the engine, so changes are seen in the
+    /// order they were written.
+    fn key_changed(&self, operation: Operation, key: &str, value: Option<&str>) {
+        self.writes.send_modify(|writes| *writes += 1);
+        self.waiters.wake(key, value);
+        if self.keyspace_notifications {
+            self.broker
+                .notify_keyspace(&operation.as_str_name().to_lowercase(), key);
+        }
     }
 }
 
-pub async fn run_proto(engine: Arc<Mutex<Box<dyn Engine>>>, command: Command) -> Response {
+pub async fn run_proto(ctx: &Context, command: Command) -> Response {
+    if let Err(e) = command.validate() {
+        return Response::error(e);
+    }
     match command {
-        Command::Get { key } => match engine.lock().await.get(&key).await {
-            Ok(value) => match value {
-                Some(v) => Response {
-                    status: Status::Ok,
-                    content: Some(v),
-                },
-                None => Response {
-                    status: Status::NotFound,
-                    content: None,
-                },
-            },
-            Err(e) => Response {
-                status: Status::Error,
-                content: Some(format!("error: {}", e)),
-            },
+        Command::Get { .. }
+        | Command::Set { .. }
+        | Command::Del { .. }
+        | Command::List
+        | Command::Dbsize
+        | Command::Flushdb => {
+            let mut engine = ctx.engine.lock().await;
+            run_on_engine(ctx, engine.as_mut(), command).await
+        }
+        // Authentication is handled by the server before commands get here,
+        // so it's only reached when the server has no users configured
+        Command::Auth { .. } => Response::error(ResponseError::new(
+            ErrorCode::Unsupported,
+            "authentication is not enabled",
+        )),
+        Command::Ping { message } => ping(message),
+        Command::Publish { channel, message } => publish(ctx, &channel, &message),
+        Command::Wait {
+            key,
+            timeout_ms,
+            changed,
+        } => wait(ctx, &key, timeout_ms, changed).await,
+        // Subscriptions and CDC streams belong to a connection, so the server handles them
+        Command::Subscribe { .. }
+        | Command::Unsubscribe { .. }
+        | Command::Psubscribe { .. }
+        | Command::Punsubscribe { .. }
+        | Command::Cdc { .. } => Response::error(ResponseError::new(
+            ErrorCode::Unsupported,
+            "streaming commands are only available over a connection",
+        )),
+        Command::Multi
+        | Command::Exec
+        | Command::Discard
+        | Command::Watch { .. }
+        | Command::Unwatch => Response::error(ResponseError::new(
+            ErrorCode::Unsupported,
+            "transactions are only available over a connection",
+        )),
+        // The settings and the slow log belong to the server
+        Command::ConfigGet { .. } | Command::ConfigSet { .. } | Command::ConfigRewrite => {
+            Response::error(ResponseError::new(
+                ErrorCode::Unsupported,
+                "configuration commands are only available on a server",
+            ))
+        }
+        Command::SlowlogGet { .. } | Command::SlowlogReset | Command::SlowlogLen => {
+            Response::error(ResponseError::new(
+                ErrorCode::Unsupported,
+                "the slow log is only available on a server",
+            ))
+        }
+        Command::Hello { version, .. } => match protocol::negotiate(version) {
+            Ok(protocol_version) => Response::hello(Hello {
+                protocol_version,
+                server_version: env!("CARGO_PKG_VERSION").to_string(),
+                features: protocol::FEATURES.iter().map(|f| f.to_string()).collect(),
+            }),
+            Err(e) => Response::error(e),
         },
-        Command::Set { key, value } => match engine.lock().await.set(&key, &value).await {
-            Ok(_) => Response {
-                status: Status::Ok,
-                content: None,
-            },
-            Err(e) => Response {
-                status: Status::Error,
-                content: Some(format!("error: {}", e)),
+        Command::Info => {
+            let mut engine = ctx.engine.lock().await;
+            match info(ctx, engine.as_mut()).await {
+                Ok(info) => Response::info(info),
+                Err(e) => Response::error(ResponseError::from(&e)),
+            }
+        }
+    }
+}
+
+/// Runs a command on an engine the caller already holds, so several of them
+/// can run as a unit. Only the commands allowed in a transaction can run here.
+pub(crate) async fn run_on_engine(
+    ctx: &Context,
+    engine: &mut dyn Engine,
+    command: Command,
+) -> Response {
+    match command {
+        Command::Get { key } => match engine.get(&key).await {
+            Ok(value) => match value {
+                Some(v) => Response::ok(Some(v)),
+                None => Response::not_found(),
             },
+            Err(e) => Response::error(ResponseError::from(&e)),
         },
-        Command::Del { key } => match engine.lock().await.delete(&key).await {
-            Ok(_) => Response {
-                status: Status::Ok,
-                content: None,
-            },
-            Err(e) => Response {
-                status: Status::Error,
-                content: Some(format!("error: {}", e)),
-            },
+        Command::Set { key, value } => match engine.set(&key, &value).await {
+            Ok(_) => {
+                ctx.key_changed(Operation::Set, &key, Some(&value));
+                Response::ok(None)
+            }
+            Err(e) => Response::error(ResponseError::from(&e)),
         },
-        Command::List => {
-            let mut result = String::new();
-            match engine.lock().await.list().await {
-                Ok(keys) => {
-                    for key in keys {
-                        result.push_str(&format!("- {}\n", key));
-                    }
-                    result.push_str("\n");
-                }
-                Err(e) => {
-                    result.push_str(&format!("error: {}\n", e));
-                }
+        Command::Del { key } => match engine.delete(&key).await {
+            Ok(_) => {
+                ctx.key_changed(Operation::Del, &key, None);
+                Response::ok(None)
             }
-            Response {
-                status: Status::Ok,
-                content: Some(result),
+            Err(e) => Response::error(ResponseError::from(&e)),
+        },
+        Command::List => match engine.list().await {
+            Ok(keys) => Response::keys(keys.into_iter().collect()),
+            Err(e) => Response::error(ResponseError::from(&e)),
+        },
+        Command::Dbsize => match engine.key_count() {
+            Ok(keys) => Response::integer(keys as i64),
+            Err(e) => Response::error(ResponseError::from(&e)),
+        },
+        Command::Flushdb => match flush(ctx, engine).await {
+            Ok(deleted) => Response::integer(deleted as i64),
+            Err(e) => Response::error(ResponseError::from(&e)),
+        },
+        Command::Ping { message } => ping(message),
+        Command::Publish { channel, message } => publish(ctx, &channel, &message),
+        command => Response::error(ResponseError::new(
+            ErrorCode::Internal,
+            format!("{} can't run in a transaction", command.name()),
+        )),
+    }
+}
+
+async fn info(ctx: &Context, engine: &mut dyn Engine) -> anyhow::Result<Info> {
+    Ok(Info {
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: ctx.started.elapsed().as_secs(),
+        engine: engine.name().to_string(),
+        file_size: engine.file_size().await?,
+        keys: engine.key_count()?,
+        connected_clients: 0,
+        memory_bytes: info::memory_usage(),
+    })
+}
+
+/// Deletes every key, returning how many there were. Each one is deleted
+/// like DEL would, so followers, waiters and subscribers see it go.
+async fn flush(ctx: &Context, engine: &mut dyn Engine) -> anyhow::Result<usize> {
+    let keys = engine.list().await?;
+    for key in &keys {
+        engine.delete(key).await?;
+        ctx.key_changed(Operation::Del, key, None);
+    }
+    Ok(keys.len())
+}
+
+fn ping(message: Option<String>) -> Response {
+    Response::ok(Some(message.unwrap_or("PONG".to_string())))
+}
+
+fn publish(ctx: &Context, channel: &str, message: &str) -> Response {
+    Response::integer(ctx.broker.publish(channel, message) as i64)
+}
+
+/// Blocks until the key is written, returning its new value. Timing out or
+/// the key being deleted return `NotFound`.
+async fn wait(ctx: &Context, key: &str, timeout_ms: u64, changed: bool) -> Response {
+    let waiter = {
+        let mut engine = ctx.engine.lock().await;
+        if !changed {
+            match engine.get(key).await {
+                Ok(Some(value)) => return Response::ok(Some(value)),
+                Ok(None) => {}
+                Err(e) => return Response::error(ResponseError::from(&e)),
             }
         }
+        // Registered while holding the engine, so no write can be missed
+        ctx.waiters.register(key)
+    };
+
+    let value = match timeout_ms {
+        0 => waiter.await,
+        _ => match tokio::time::timeout(Duration::from_millis(timeout_ms), waiter).await {
+            Ok(value) => value,
+            Err(_) => return Response::not_found(),
+        },
+    };
+    match value {
+        Ok(Some(value)) => Response::ok(Some(value)),
+        _ => Response::not_found(),
     }
 }
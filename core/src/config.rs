@@ -1,14 +1,302 @@
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::pattern;
+use crate::response::{ErrorCode, ResponseError};
+
+/// Parameters CONFIG SET can change while the server is running. The rest
+/// only take effect on restart.
+pub const RUNTIME_PARAMETERS: [&str; 9] = [
+    "log_level",
+    "shutdown_timeout_secs",
+    "limits.max_clients",
+    "limits.idle_timeout_secs",
+    "limits.read_timeout_secs",
+    "limits.write_timeout_secs",
+    "limits.max_request_size",
+    "slowlog.threshold_micros",
+    "slowlog.max_len",
+];
+
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub file_path: String,
+    /// Addresses the server listens on (IPv4 or IPv6). Entries without a
+    /// port use the one given with `--port`.
+    #[serde(default = "default_bind")]
+    pub bind: Vec<String>,
+    /// Seconds in-flight commands are given to finish when the server is
+    /// shutting down
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Publishes a message to `__keyspace__:<key>` whenever a key is set or
+    /// deleted, with the operation as the payload
+    #[serde(default)]
+    pub keyspace_notifications: bool,
+    /// Most verbose level logged: off, error, warn, info, debug or trace
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// How the logs are written: text, or json with one object per line.
+    /// Values are only logged at the debug and trace levels
+    #[serde(default = "default_log_format")]
+    pub log_format: String,
+    /// Serves the TCP listeners over TLS when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// File with the user accounts. Clients must authenticate when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub users_file: Option<String>,
+    /// Serves Prometheus metrics over HTTP at `/metrics` on this address
+    /// (`ip:port`) when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_address: Option<String>,
+    /// Also listens on a Unix domain socket when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<UnixSocketConfig>,
+    /// Records the commands that change the database or the server when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditConfig>,
+    /// Limits applied to client connections
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// Log of the commands slower than a threshold
+    #[serde(default)]
+    pub slowlog: SlowLogConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Maximum number of connected clients. Connections over the limit are rejected
+    pub max_clients: usize,
+    /// Seconds a connection can stay without sending a command before it's
+    /// closed (0 disables it)
+    pub idle_timeout_secs: u64,
+    /// Seconds a client has to finish sending a command once it started (0 disables it)
+    pub read_timeout_secs: u64,
+    /// Seconds a client has to read a response before the connection is
+    /// closed, for clients that stop reading their socket (0 disables it)
+    pub write_timeout_secs: u64,
+    /// Maximum size in bytes of a single request
+    pub max_request_size: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_clients: 1024,
+            idle_timeout_secs: 300,
+            read_timeout_secs: 30,
+            write_timeout_secs: 30,
+            max_request_size: 1024 * 1024,
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs))
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        (self.read_timeout_secs > 0).then(|| Duration::from_secs(self.read_timeout_secs))
+    }
+
+    pub fn write_timeout(&self) -> Option<Duration> {
+        (self.write_timeout_secs > 0).then(|| Duration::from_secs(self.write_timeout_secs))
+    }
 }
 
-#[derive(Debug)]
-struct Thing;
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SlowLogConfig {
+    /// Commands taking at least this many microseconds are logged (0 logs all of them)
+    pub threshold_micros: u64,
+    /// Number of entries kept, the oldest are dropped first (0 disables the log)
+    pub max_len: usize,
+}
+
+impl Default for SlowLogConfig {
+    fn default() -> Self {
+        SlowLogConfig {
+            threshold_micros: 10_000,
+            max_len: 128,
+        }
+    }
+}
+
+impl SlowLogConfig {
+    pub fn threshold(&self) -> Duration {
+        Duration::from_micros(self.threshold_micros)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnixSocketConfig {
+    /// Path of the socket file
+    pub path: String,
+    /// Octal permissions of the socket file (e.g. "660")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditConfig {
+    /// File the entries are appended to, one JSON object per line
+    pub path: String,
+    /// Also records the values written by SET
+    #[serde(default)]
+    pub log_values: bool,
+    /// Size in bytes the file can grow to before it's rotated (0 disables rotation)
+    #[serde(default = "default_audit_max_size")]
+    pub max_size: u64,
+    /// Number of rotated files kept, named after the file with a `.1`, `.2`,
+    /// ... suffix, the oldest being the last
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM file with the server certificate chain
+    pub cert_file: String,
+    /// PEM file with the server private key
+    pub key_file: String,
+    /// PEM file with the CAs trusted to sign client certificates. When set,
+    /// clients must present a valid certificate (mTLS).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_file: Option<String>,
+}
+
+fn default_bind() -> Vec<String> {
+    vec!["127.0.0.1".to_string()]
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_format() -> String {
+    "text".to_string()
+}
+
+fn default_audit_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_audit_max_files() -> usize {
+    5
+}
+
+impl Config {
+    /// Returns the parameters whose name matches the glob pattern, with their
+    /// values. Nested settings are named after their table, such as
+    /// `limits.max_clients`.
+    pub fn get(&self, pattern: &str) -> anyhow::Result<BTreeMap<String, String>> {
+        let mut parameters = BTreeMap::new();
+        flatten("", &toml::Value::try_from(self)?, &mut parameters);
+        parameters.retain(|name, _| pattern::matches(pattern, name));
+        Ok(parameters
+            .into_iter()
+            .map(|(name, value)| match value {
+                toml::Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect())
+    }
+
+    /// Changes one of the `RUNTIME_PARAMETERS`, parsing the value as the
+    /// type of the setting.
+    pub fn set(&mut self, parameter: &str, value: &str) -> Result<(), ResponseError> {
+        if !RUNTIME_PARAMETERS.contains(&parameter) {
+            return Err(invalid(match self.get(parameter) {
+                Ok(parameters) if !parameters.is_empty() => {
+                    format!("{} can't be changed at runtime", parameter)
+                }
+                _ => format!("unknown parameter '{}'", parameter),
+            }));
+        }
+
+        let mut root = toml::Value::try_from(&*self).map_err(|e| invalid(e.to_string()))?;
+        let mut current = &mut root;
+        for name in parameter.split('.') {
+            current = current
+                .get_mut(name)
+                .ok_or_else(|| invalid(format!("unknown parameter '{}'", parameter)))?;
+        }
+        let parsed = match current {
+            toml::Value::Integer(_) => value.parse().ok().map(toml::Value::Integer),
+            toml::Value::Boolean(_) => value.parse().ok().map(toml::Value::Boolean),
+            _ => Some(toml::Value::String(value.to_string())),
+        };
+        *current = parsed.ok_or_else(|| {
+            invalid(format!(
+                "invalid {} '{}' for {}",
+                current.type_str(),
+                value,
+                parameter
+            ))
+        })?;
+
+        let config: Config = root.try_into().map_err(|e| invalid(e.to_string()))?;
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Checks the settings have values the server can run with.
+    pub fn validate(&self) -> Result<(), ResponseError> {
+        if !LOG_LEVELS.contains(&self.log_level.as_str()) {
+            return Err(invalid(format!(
+                "log_level must be one of {}",
+                LOG_LEVELS.join(", ")
+            )));
+        }
+        if !LOG_FORMATS.contains(&self.log_format.as_str()) {
+            return Err(invalid(format!(
+                "log_format must be one of {}",
+                LOG_FORMATS.join(", ")
+            )));
+        }
+        if self.limits.max_clients == 0 {
+            return Err(invalid("limits.max_clients must be at least 1"));
+        }
+        if self.limits.max_request_size == 0 {
+            return Err(invalid("limits.max_request_size must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Collects the values of the table, naming the nested ones after their path.
+fn flatten(prefix: &str, value: &toml::Value, parameters: &mut BTreeMap<String, toml::Value>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (name, value) in table {
+                let name = match prefix {
+                    "" => name.to_string(),
+                    _ => format!("{}.{}", prefix, name),
+                };
+                flatten(&name, value, parameters);
+            }
+        }
+        value => {
+            parameters.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ResponseError {
+    ResponseError::new(ErrorCode::InvalidArgument, message)
+}
 
 /// Opens the config file and returns the Config struct
 /// If the file doesn't exist, it creates it with the default values
@@ -29,13 +317,11 @@ pub fn parse() -> Result<Config, anyhow::Error> {
 
     let db_file = db_dir.join("tuna.db");
 
-    let config_file: PathBuf;
-
     if !config_dir.exists() {
         let _ = fs::create_dir_all(&config_dir);
     }
 
-    config_file = config_dir.join("config.toml");
+    let config_file: PathBuf = config_dir.join("config.toml");
 
     if !config_file.exists() {
         let default_config = Config {
@@ -43,6 +329,18 @@ pub fn parse() -> Result<Config, anyhow::Error> {
                 .to_str()
                 .ok_or(anyhow!("db file path coulnd't be stringifyed"))?
                 .to_string(),
+            bind: default_bind(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            keyspace_notifications: false,
+            log_level: default_log_level(),
+            log_format: default_log_format(),
+            tls: None,
+            users_file: None,
+            metrics_address: None,
+            unix_socket: None,
+            audit: None,
+            limits: LimitsConfig::default(),
+            slowlog: SlowLogConfig::default(),
         };
 
         let toml = toml::to_string(&default_config)?;
@@ -64,20 +362,26 @@ pub fn parse() -> Result<Config, anyhow::Error> {
             .ok_or(anyhow!("config file couldn't be parsed as string"))?,
     )?;
 
-    let config = toml::from_str(&contents)?;
+    let config: Config = toml::from_str(&contents)?;
+    config.validate()?;
     Ok(config)
 }
 
 pub fn set_file_path(file_path: String) -> anyhow::Result<()> {
+    let mut config = parse()?;
+    config.file_path = file_path;
+    save(&config)
+}
+
+/// Writes the given config to the config file, replacing its contents
+pub fn save(config: &Config) -> anyhow::Result<()> {
     let config_dir = home::home_dir()
         .ok_or(anyhow!("home dir coundn't be found"))?
         .join(".config")
         .join("tunadb");
     let config_file = config_dir.join("config.toml");
 
-    let config = Config { file_path };
-
-    let toml = toml::to_string(&config)?;
+    let toml = toml::to_string(config)?;
     fs::write(
         config_file
             .to_str()
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use anyhow::anyhow;
+
+use crate::command::{validate_key, validate_value};
+use crate::storage::{self, Engine};
+
+/// Settings of an embedded database.
+#[derive(Debug, Clone)]
+pub struct DbOptions {
+    /// Creates the database file if it doesn't exist yet
+    pub create_if_missing: bool,
+    /// Waits for every write to reach the disk before returning
+    pub sync_writes: bool,
+}
+
+impl Default for DbOptions {
+    fn default() -> Self {
+        DbOptions {
+            create_if_missing: true,
+            sync_writes: false,
+        }
+    }
+}
+
+/**
+* Database opened in-process, without a server. It doesn't need a Tokio
+* runtime, so it can be used from sync code.
+*
+* Cloning a Db returns a handle to the same database, and the handles can
+* be used from multiple threads at the same time. Writes are serialized.
+*
+* Example:
+*   let db = Db::open("tuna.db", DbOptions::default())?;
+*   db.set("key", "value")?;
+*   for (key, value) in db.iter()? {
+*       println!("{} = {}", key, value);
+*   }
+*   db.close()?;
+*/
+#[derive(Clone)]
+pub struct Db {
+    /// `None` once the database is closed
+    engine: Arc<Mutex<Option<Box<dyn Engine>>>>,
+    options: DbOptions,
+}
+
+/// Iterator over a snapshot of the key-value pairs, sorted by key.
+pub struct Iter {
+    entries: std::collections::btree_map::IntoIter<String, String>,
+}
+
+impl Db {
+    pub fn open(path: impl AsRef<Path>, options: DbOptions) -> anyhow::Result<Db> {
+        let path = path.as_ref();
+        if !options.create_if_missing && !path.exists() {
+            return Err(anyhow!("database file {} doesn't exist", path.display()));
+        }
+        let path = path
+            .to_str()
+            .ok_or(anyhow!("database file path isn't valid UTF-8"))?;
+        Ok(Db {
+            engine: Arc::new(Mutex::new(Some(storage::new_engine(path)?))),
+            options,
+        })
+    }
+
+    /// Returns the value of the key, `None` if it doesn't exist.
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.with_engine(|engine| block_on(engine.get(key)))
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        validate_key(key)?;
+        validate_value(value)?;
+        self.with_engine(|engine| {
+            block_on(engine.set(key, value))?;
+            self.written(engine)
+        })
+    }
+
+    pub fn delete(&self, key: &str) -> anyhow::Result<()> {
+        validate_key(key)?;
+        self.with_engine(|engine| {
+            block_on(engine.delete(key))?;
+            self.written(engine)
+        })
+    }
+
+    /// Returns every key, in no particular order.
+    pub fn keys(&self) -> anyhow::Result<Vec<String>> {
+        self.with_engine(|engine| Ok(block_on(engine.list())?.into_iter().collect()))
+    }
+
+    /// Iterates over the key-value pairs as they are when it's called.
+    /// Later writes don't change what it returns.
+    pub fn iter(&self) -> anyhow::Result<Iter> {
+        self.with_engine(|engine| {
+            let mut entries = BTreeMap::new();
+            let mut position = 0;
+            loop {
+                let records = block_on(engine.read_log(position, crate::cdc::BATCH_SIZE))?;
+                let Some(last) = records.last() else {
+                    break;
+                };
+                position = last.sequence;
+                for record in records {
+                    match record.value {
+                        Some(value) => entries.insert(record.key, value),
+                        None => entries.remove(&record.key),
+                    };
+                }
+            }
+            Ok(Iter {
+                entries: entries.into_iter(),
+            })
+        })
+    }
+
+    /// Waits until every write is persisted to disk.
+    pub fn sync(&self) -> anyhow::Result<()> {
+        self.with_engine(|engine| Ok(block_on(engine.sync())?))
+    }
+
+    /// Persists the pending writes and closes the database. Every handle
+    /// fails afterwards.
+    pub fn close(&self) -> anyhow::Result<()> {
+        let Some(mut engine) = self.engine.lock().unwrap().take() else {
+            return Ok(());
+        };
+        Ok(block_on(engine.sync())?)
+    }
+
+    fn with_engine<T>(
+        &self,
+        f: impl FnOnce(&mut dyn Engine) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut engine = self.engine.lock().unwrap();
+        match engine.as_mut() {
+            Some(engine) => f(engine.as_mut()),
+            None => Err(anyhow!("database is closed")),
+        }
+    }
+
+    fn written(&self, engine: &mut dyn Engine) -> anyhow::Result<()> {
+        if self.options.sync_writes {
+            block_on(engine.sync())?;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Iter {
+    type Item = (String, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+}
+
+/// Wakes up the thread blocked on a future.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion on the current thread. The engine only
+/// awaits its own locks, so it doesn't need a runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the big-endian length that prefixes every frame.
+pub const FRAME_LENGTH_SIZE: usize = 4;
+
+/**
+* Messages are exchanged over the socket as length-prefixed frames, so
+* the reader knows where a message ends no matter how the bytes are split
+* by the transport.
+*
+* The frame format is as follows:
+* bytes 0..4: length of the payload (4 bytes, big-endian)
+* bytes 4..n+4: payload (an encoded command or response)
+*/
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+
+    let mut bytes = Vec::with_capacity(FRAME_LENGTH_SIZE + payload.len());
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(payload);
+
+    writer.write_all(&bytes).await?;
+    writer.flush().await
+}
+
+/// Reads the next frame payload. Returns `None` if the stream was closed
+/// before a new frame started.
+///
+/// Frames longer than `max_length` fail with `InvalidData` before their
+/// payload is read, so the stream can't be used anymore.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_length: usize,
+) -> io::Result<Option<Vec<u8>>> {
+    let Some(length) = read_frame_length(reader).await? else {
+        return Ok(None);
+    };
+    if length > max_length {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "frame of {} bytes is over the limit of {} bytes",
+                length, max_length
+            ),
+        ));
+    }
+
+    Ok(Some(read_frame_payload(reader, length).await?))
+}
+
+/// Reads the length prefix of the next frame. Returns `None` if the stream
+/// was closed before a new frame started.
+pub async fn read_frame_length<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<usize>> {
+    let mut length_buffer = [0; FRAME_LENGTH_SIZE];
+    let mut read = 0;
+
+    while read < FRAME_LENGTH_SIZE {
+        let n = reader.read(&mut length_buffer[read..]).await?;
+        if n == 0 {
+            if read == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        read += n;
+    }
+
+    Ok(Some(u32::from_be_bytes(length_buffer) as usize))
+}
+
+/// Reads the payload of a frame whose length prefix was already read. The
+/// buffer grows as the bytes arrive, so a bogus length can't make it
+/// allocate more than what's actually sent.
+pub async fn read_frame_payload<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    length: usize,
+) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    reader.take(length as u64).read_to_end(&mut payload).await?;
+    if payload.len() < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, payload).await.unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn frames_round_trip() {
+        let mut bytes = frame(b"first").await;
+        bytes.extend(frame(b"second").await);
+
+        let mut reader = &bytes[..];
+        assert_eq!(
+            read_frame(&mut reader, 1024).await.unwrap().unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            read_frame(&mut reader, 1024).await.unwrap().unwrap(),
+            b"second"
+        );
+        assert_eq!(read_frame(&mut reader, 1024).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn zero_length_frames_round_trip() {
+        let bytes = frame(b"").await;
+        assert_eq!(bytes, [0, 0, 0, 0]);
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_frame(&mut reader, 0).await.unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut reader, 0).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn truncated_length_prefixes_fail() {
+        let mut reader: &[u8] = &[0, 0];
+        let error = read_frame(&mut reader, 1024).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn truncated_payloads_fail() {
+        let bytes = frame(b"payload").await;
+        let mut reader = &bytes[..bytes.len() - 1];
+        let error = read_frame(&mut reader, 1024).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn frames_over_the_max_length_are_rejected_before_their_payload() {
+        let bytes = frame(b"payload").await;
+        let mut reader = &bytes[..];
+        let error = read_frame(&mut reader, 6).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(reader, b"payload");
+
+        let mut reader = &bytes[..];
+        assert_eq!(
+            read_frame(&mut reader, 7).await.unwrap().unwrap(),
+            b"payload"
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_length_prefixes_fail_without_allocating_them() {
+        let mut reader: &[u8] = &[0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+        let error = read_frame(&mut reader, 1024).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Without a limit, the payload is cut short long before 4 GiB are allocated
+        let mut reader: &[u8] = &[1, 2, 3];
+        let error = read_frame_payload(&mut reader, u32::MAX as usize)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
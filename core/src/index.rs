@@ -8,6 +8,7 @@ use std::{
 };
 use tokio::sync::Mutex;
 
+use crate::metrics::METRICS;
 use crate::storage::VALUE_LENGTH_SIZE;
 
 #[async_trait]
@@ -15,6 +16,8 @@ pub trait OffsetIndexer: Send {
     async fn get(&self, key: &str) -> Result<Option<String>, Error>;
     async fn set(&mut self, key: &str, offset: u64);
     async fn delete(&mut self, key: &str);
+    /// Number of keys in the index.
+    fn entries(&self) -> usize;
 }
 
 #[derive(Clone)]
@@ -53,11 +56,11 @@ impl OffsetIndexer for BinaryOffsetIndexer {
         let _ = self.file.lock().await.read_exact(&mut value_length_buffer);
         let value_length = u16::from_be_bytes(value_length_buffer);
 
-        let mut current_value: Vec<u8> = Vec::with_capacity(value_length as usize);
-        current_value.resize(value_length as usize, 0);
+        let mut current_value: Vec<u8> = vec![0; value_length as usize];
 
         let _ = self.file.lock().await.read_exact(&mut current_value);
         let value_str = String::from_utf8(current_value)?;
+        METRICS.storage_read((VALUE_LENGTH_SIZE + value_length as usize) as u64);
 
         Ok(Some(value_str))
     }
@@ -69,4 +72,8 @@ impl OffsetIndexer for BinaryOffsetIndexer {
     async fn delete(&mut self, key: &str) {
         self.offsets.remove(key);
     }
+
+    fn entries(&self) -> usize {
+        self.offsets.len()
+    }
 }
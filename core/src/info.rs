@@ -0,0 +1,30 @@
+use std::fs;
+
+/// Reply of the server to INFO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Info {
+    pub server_version: String,
+    pub uptime_secs: u64,
+    /// Storage engine serving the database file
+    pub engine: String,
+    /// Size of the database file in bytes
+    pub file_size: u64,
+    pub keys: u64,
+    /// Only known by the server, 0 when commands run without one
+    pub connected_clients: u64,
+    /// Resident memory of the process in bytes, if it can be measured
+    pub memory_bytes: Option<u64>,
+}
+
+/// Returns the resident memory of the current process in bytes. Only
+/// supported on Linux, where it's reported in KiB whatever the page size.
+pub fn memory_usage() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let resident = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?;
+    let kib: u64 = resident.trim().parse().ok()?;
+    Some(kib * 1024)
+}
@@ -1,9 +1,20 @@
+pub mod cdc;
 pub mod command;
 pub mod config;
+pub mod db;
+pub mod frame;
 pub mod index;
+pub mod info;
+pub mod metrics;
+pub mod pattern;
+pub mod protocol;
+pub mod pubsub;
 pub mod response;
 pub mod serializer;
+pub mod slowlog;
 pub mod storage;
+pub mod transaction;
+pub mod wait;
 
 pub mod proto {
     include!("./protobuf/command.rs");
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds in seconds of the buckets of the latency histograms.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Metrics of the whole process, updated by the storage engine and the server.
+pub static METRICS: Metrics = Metrics::new();
+
+/**
+* Counters and histograms exported in the Prometheus text format. Values
+* that can be read at any time, such as the file size, aren't kept here:
+* they're measured when the metrics are rendered.
+*/
+pub struct Metrics {
+    /// Commands run, by name and status
+    commands: Mutex<BTreeMap<(String, &'static str), u64>>,
+    /// Time taken by the commands, by name
+    latencies: Mutex<BTreeMap<String, Histogram>>,
+    /// Bytes read from and written to the database file
+    storage_read: AtomicU64,
+    storage_written: AtomicU64,
+    /// Bytes received from and sent to clients
+    network_received: AtomicU64,
+    network_sent: AtomicU64,
+    /// Lookups answered by the index, and the ones that had to scan the file
+    index_hits: AtomicU64,
+    index_misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Not cumulative, the last one counts the values over every bound
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Metrics {
+            commands: Mutex::new(BTreeMap::new()),
+            latencies: Mutex::new(BTreeMap::new()),
+            storage_read: AtomicU64::new(0),
+            storage_written: AtomicU64::new(0),
+            network_received: AtomicU64::new(0),
+            network_sent: AtomicU64::new(0),
+            index_hits: AtomicU64::new(0),
+            index_misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a command that finished, successfully or not, and its latency.
+    pub fn command(&self, name: &str, ok: bool, duration: Duration) {
+        let status = if ok { "ok" } else { "error" };
+        *self
+            .commands
+            .lock()
+            .unwrap()
+            .entry((name.to_string(), status))
+            .or_default() += 1;
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn storage_read(&self, bytes: u64) {
+        self.storage_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn storage_written(&self, bytes: u64) {
+        self.storage_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn network_received(&self, bytes: u64) {
+        self.network_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn network_sent(&self, bytes: u64) {
+        self.network_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn index_hit(&self) {
+        self.index_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn index_miss(&self) {
+        self.index_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Appends the counters and histograms to `out` in the Prometheus text format.
+    pub fn render(&self, out: &mut String) {
+        header(
+            out,
+            "tuna_commands_total",
+            "counter",
+            "Commands run, by name and status",
+        );
+        for ((command, status), count) in self.commands.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "tuna_commands_total{{command=\"{}\",status=\"{}\"}} {}",
+                command, status, count
+            );
+        }
+
+        let name = "tuna_command_duration_seconds";
+        header(out, name, "histogram", "Time taken to run the commands");
+        for (command, histogram) in self.latencies.lock().unwrap().iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{command=\"{}\",le=\"{}\"}} {}",
+                    name, command, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{command=\"{}\",le=\"+Inf\"}} {}",
+                name, command, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "{}_sum{{command=\"{}\"}} {}",
+                name, command, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "{}_count{{command=\"{}\"}} {}",
+                name, command, histogram.count
+            );
+        }
+
+        let counters = [
+            (
+                "tuna_storage_read_bytes_total",
+                "Bytes read from the database file",
+                &self.storage_read,
+            ),
+            (
+                "tuna_storage_written_bytes_total",
+                "Bytes written to the database file",
+                &self.storage_written,
+            ),
+            (
+                "tuna_network_received_bytes_total",
+                "Bytes received from clients",
+                &self.network_received,
+            ),
+            (
+                "tuna_network_sent_bytes_total",
+                "Bytes sent to clients",
+                &self.network_sent,
+            ),
+            (
+                "tuna_index_hits_total",
+                "Lookups answered by the index",
+                &self.index_hits,
+            ),
+            (
+                "tuna_index_misses_total",
+                "Lookups that had to scan the database file",
+                &self.index_misses,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            header(out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+    }
+}
+
+/// Appends a gauge to `out` in the Prometheus text format.
+pub fn render_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, "gauge", help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,77 @@
+/// Matches `text` against a glob-style pattern, where `*` matches any
+/// sequence of characters (including none) and `?` matches exactly one.
+///
+/// # Example
+/// ```
+/// use tuna_core::pattern;
+///
+/// assert!(pattern::matches("cache:*", "cache:users"));
+/// assert!(!pattern::matches("cache:?", "cache:users"));
+/// ```
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            // Let the last `*` consume one more character and try again
+            p = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn star_matches_any_sequence() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "cache:users"));
+        assert!(matches("cache:*", "cache:"));
+        assert!(matches("*:users", "cache:users"));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(matches("a*b", "abcb"));
+        assert!(!matches("a*b", "abc"));
+        assert!(!matches("cache:*", "jobs:1"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("job?", "jobs"));
+        assert!(matches("?", "é"));
+        assert!(!matches("?", ""));
+        assert!(!matches("job?", "job"));
+        assert!(!matches("job?", "jobs1"));
+    }
+
+    #[test]
+    fn trailing_stars_match_nothing() {
+        assert!(matches("jobs**", "jobs"));
+        assert!(matches("jobs*?*", "jobs1"));
+        assert!(!matches("jobs*?*", "jobs"));
+    }
+
+    #[test]
+    fn empty_pattern_only_matches_empty_text() {
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(!matches("a", ""));
+    }
+}
@@ -8,6 +8,26 @@ pub struct Command {
     pub key: ::prost::alloc::string::String,
     #[prost(string, optional, tag = "3")]
     pub value: ::core::option::Option<::prost::alloc::string::String>,
+    /// Arguments of the commands that take a list, such as the channels of
+    /// SUBSCRIBE or the keys of WATCH
+    #[prost(string, repeated, tag = "4")]
+    pub args: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Log position CDC starts streaming from
+    #[prost(uint64, optional, tag = "5")]
+    pub position: ::core::option::Option<u64>,
+    /// Milliseconds WAIT blocks for at most, 0 waits forever
+    #[prost(uint64, optional, tag = "6")]
+    pub timeout_ms: ::core::option::Option<u64>,
+    /// Makes WAIT ignore the current value and wait for the next write
+    #[prost(bool, tag = "7")]
+    pub changed: bool,
+    /// Latest protocol version the client speaks, sent with HELLO along with
+    /// its features in args
+    #[prost(uint32, optional, tag = "8")]
+    pub protocol_version: ::core::option::Option<u32>,
+    /// Maximum number of entries SLOWLOG GET returns
+    #[prost(uint32, optional, tag = "9")]
+    pub count: ::core::option::Option<u32>,
 }
 /// Nested message and enum types in `Command`.
 pub mod command {
@@ -28,6 +48,30 @@ pub mod command {
         Set = 1,
         Del = 2,
         List = 4,
+        Auth = 5,
+        Ping = 6,
+        Subscribe = 7,
+        Unsubscribe = 8,
+        Psubscribe = 9,
+        Punsubscribe = 10,
+        Publish = 11,
+        Cdc = 12,
+        Wait = 13,
+        Multi = 14,
+        Exec = 15,
+        Discard = 16,
+        Watch = 17,
+        Unwatch = 18,
+        Hello = 19,
+        Info = 20,
+        Dbsize = 21,
+        Flushdb = 22,
+        ConfigGet = 23,
+        ConfigSet = 24,
+        ConfigRewrite = 25,
+        SlowlogGet = 26,
+        SlowlogReset = 27,
+        SlowlogLen = 28,
     }
     impl Operation {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -40,6 +84,30 @@ pub mod command {
                 Operation::Set => "SET",
                 Operation::Del => "DEL",
                 Operation::List => "LIST",
+                Operation::Auth => "AUTH",
+                Operation::Ping => "PING",
+                Operation::Subscribe => "SUBSCRIBE",
+                Operation::Unsubscribe => "UNSUBSCRIBE",
+                Operation::Psubscribe => "PSUBSCRIBE",
+                Operation::Punsubscribe => "PUNSUBSCRIBE",
+                Operation::Publish => "PUBLISH",
+                Operation::Cdc => "CDC",
+                Operation::Wait => "WAIT",
+                Operation::Multi => "MULTI",
+                Operation::Exec => "EXEC",
+                Operation::Discard => "DISCARD",
+                Operation::Watch => "WATCH",
+                Operation::Unwatch => "UNWATCH",
+                Operation::Hello => "HELLO",
+                Operation::Info => "INFO",
+                Operation::Dbsize => "DBSIZE",
+                Operation::Flushdb => "FLUSHDB",
+                Operation::ConfigGet => "CONFIG_GET",
+                Operation::ConfigSet => "CONFIG_SET",
+                Operation::ConfigRewrite => "CONFIG_REWRITE",
+                Operation::SlowlogGet => "SLOWLOG_GET",
+                Operation::SlowlogReset => "SLOWLOG_RESET",
+                Operation::SlowlogLen => "SLOWLOG_LEN",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -49,6 +117,30 @@ pub mod command {
                 "SET" => Some(Self::Set),
                 "DEL" => Some(Self::Del),
                 "LIST" => Some(Self::List),
+                "AUTH" => Some(Self::Auth),
+                "PING" => Some(Self::Ping),
+                "SUBSCRIBE" => Some(Self::Subscribe),
+                "UNSUBSCRIBE" => Some(Self::Unsubscribe),
+                "PSUBSCRIBE" => Some(Self::Psubscribe),
+                "PUNSUBSCRIBE" => Some(Self::Punsubscribe),
+                "PUBLISH" => Some(Self::Publish),
+                "CDC" => Some(Self::Cdc),
+                "WAIT" => Some(Self::Wait),
+                "MULTI" => Some(Self::Multi),
+                "EXEC" => Some(Self::Exec),
+                "DISCARD" => Some(Self::Discard),
+                "WATCH" => Some(Self::Watch),
+                "UNWATCH" => Some(Self::Unwatch),
+                "HELLO" => Some(Self::Hello),
+                "INFO" => Some(Self::Info),
+                "DBSIZE" => Some(Self::Dbsize),
+                "FLUSHDB" => Some(Self::Flushdb),
+                "CONFIG_GET" => Some(Self::ConfigGet),
+                "CONFIG_SET" => Some(Self::ConfigSet),
+                "CONFIG_REWRITE" => Some(Self::ConfigRewrite),
+                "SLOWLOG_GET" => Some(Self::SlowlogGet),
+                "SLOWLOG_RESET" => Some(Self::SlowlogReset),
+                "SLOWLOG_LEN" => Some(Self::SlowlogLen),
                 _ => None,
             }
         }
@@ -6,9 +6,196 @@ pub struct Response {
     pub status: i32,
     #[prost(string, optional, tag = "2")]
     pub content: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "3")]
+    pub error: ::core::option::Option<response::Error>,
+    /// Keys returned by LIST, in no particular order.
+    #[prost(string, repeated, tag = "4")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Set on messages pushed by the server, instead of replying to a command
+    #[prost(message, optional, tag = "5")]
+    pub message: ::core::option::Option<response::Message>,
+    /// Integer replies, such as the number of subscriptions or receivers
+    #[prost(int64, optional, tag = "6")]
+    pub integer: ::core::option::Option<i64>,
+    /// Pushed to CDC followers, in the order of the log
+    #[prost(message, repeated, tag = "7")]
+    pub changes: ::prost::alloc::vec::Vec<response::Change>,
+    /// Responses of the commands run by EXEC, in the order they were queued
+    #[prost(message, repeated, tag = "8")]
+    pub results: ::prost::alloc::vec::Vec<Response>,
+    #[prost(message, optional, tag = "9")]
+    pub hello: ::core::option::Option<response::Hello>,
+    #[prost(message, optional, tag = "10")]
+    pub info: ::core::option::Option<response::Info>,
+    /// Sorted by name
+    #[prost(message, repeated, tag = "11")]
+    pub parameters: ::prost::alloc::vec::Vec<response::Parameter>,
+    /// Returned by SLOWLOG GET, newest first
+    #[prost(message, repeated, tag = "12")]
+    pub slowlog: ::prost::alloc::vec::Vec<response::SlowLogEntry>,
 }
 /// Nested message and enum types in `Response`.
 pub mod response {
+    /// Machine-readable description of a failed command. Only set when the
+    /// status is ERROR.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Error {
+        #[prost(enumeration = "error::Code", tag = "1")]
+        pub code: i32,
+        #[prost(string, tag = "2")]
+        pub message: ::prost::alloc::string::String,
+    }
+    /// Nested message and enum types in `Error`.
+    pub mod error {
+        #[derive(
+            Clone,
+            Copy,
+            Debug,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            ::prost::Enumeration
+        )]
+        #[repr(i32)]
+        pub enum Code {
+            Unknown = 0,
+            InvalidArgument = 1,
+            KeyTooLarge = 2,
+            ValueTooLarge = 3,
+            Corruption = 4,
+            Io = 5,
+            Unauthorized = 6,
+            Unsupported = 7,
+            Internal = 8,
+            TooManyClients = 9,
+            RequestTooLarge = 10,
+            Aborted = 11,
+            IncompatibleProtocol = 12,
+        }
+        impl Code {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Code::Unknown => "UNKNOWN",
+                    Code::InvalidArgument => "INVALID_ARGUMENT",
+                    Code::KeyTooLarge => "KEY_TOO_LARGE",
+                    Code::ValueTooLarge => "VALUE_TOO_LARGE",
+                    Code::Corruption => "CORRUPTION",
+                    Code::Io => "IO",
+                    Code::Unauthorized => "UNAUTHORIZED",
+                    Code::Unsupported => "UNSUPPORTED",
+                    Code::Internal => "INTERNAL",
+                    Code::TooManyClients => "TOO_MANY_CLIENTS",
+                    Code::RequestTooLarge => "REQUEST_TOO_LARGE",
+                    Code::Aborted => "ABORTED",
+                    Code::IncompatibleProtocol => "INCOMPATIBLE_PROTOCOL",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "UNKNOWN" => Some(Self::Unknown),
+                    "INVALID_ARGUMENT" => Some(Self::InvalidArgument),
+                    "KEY_TOO_LARGE" => Some(Self::KeyTooLarge),
+                    "VALUE_TOO_LARGE" => Some(Self::ValueTooLarge),
+                    "CORRUPTION" => Some(Self::Corruption),
+                    "IO" => Some(Self::Io),
+                    "UNAUTHORIZED" => Some(Self::Unauthorized),
+                    "UNSUPPORTED" => Some(Self::Unsupported),
+                    "INTERNAL" => Some(Self::Internal),
+                    "TOO_MANY_CLIENTS" => Some(Self::TooManyClients),
+                    "REQUEST_TOO_LARGE" => Some(Self::RequestTooLarge),
+                    "ABORTED" => Some(Self::Aborted),
+                    "INCOMPATIBLE_PROTOCOL" => Some(Self::IncompatibleProtocol),
+                    _ => None,
+                }
+            }
+        }
+    }
+    /// Message pushed to a subscribed client
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Message {
+        #[prost(string, tag = "1")]
+        pub channel: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "2")]
+        pub pattern: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, tag = "3")]
+        pub payload: ::prost::alloc::string::String,
+    }
+    /// Record of the log streamed by CDC
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Change {
+        #[prost(uint64, tag = "1")]
+        pub sequence: u64,
+        /// "set" or "del"
+        #[prost(string, tag = "2")]
+        pub operation: ::prost::alloc::string::String,
+        #[prost(string, tag = "3")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "4")]
+        pub value: ::core::option::Option<::prost::alloc::string::String>,
+    }
+    /// Reply to HELLO
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Hello {
+        #[prost(uint32, tag = "1")]
+        pub protocol_version: u32,
+        #[prost(string, tag = "2")]
+        pub server_version: ::prost::alloc::string::String,
+        #[prost(string, repeated, tag = "3")]
+        pub features: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    }
+    /// Reply to INFO
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Info {
+        #[prost(string, tag = "1")]
+        pub server_version: ::prost::alloc::string::String,
+        #[prost(uint64, tag = "2")]
+        pub uptime_secs: u64,
+        /// Storage engine serving the database file
+        #[prost(string, tag = "3")]
+        pub engine: ::prost::alloc::string::String,
+        /// Size of the database file in bytes
+        #[prost(uint64, tag = "4")]
+        pub file_size: u64,
+        #[prost(uint64, tag = "5")]
+        pub keys: u64,
+        #[prost(uint64, tag = "6")]
+        pub connected_clients: u64,
+        /// Resident memory of the server process in bytes, if it can be measured
+        #[prost(uint64, optional, tag = "7")]
+        pub memory_bytes: ::core::option::Option<u64>,
+    }
+    /// Setting returned by CONFIG GET
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Parameter {
+        #[prost(string, tag = "1")]
+        pub name: ::prost::alloc::string::String,
+        #[prost(string, tag = "2")]
+        pub value: ::prost::alloc::string::String,
+    }
+    /// Command logged by the slow log
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SlowLogEntry {
+        #[prost(uint64, tag = "1")]
+        pub id: u64,
+        /// Seconds since the Unix epoch
+        #[prost(uint64, tag = "2")]
+        pub timestamp: u64,
+        #[prost(uint64, tag = "3")]
+        pub duration_micros: u64,
+        #[prost(string, tag = "4")]
+        pub command: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "5")]
+        pub key: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, tag = "6")]
+        pub client: ::prost::alloc::string::String,
+    }
     #[derive(
         Clone,
         Copy,
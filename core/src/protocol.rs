@@ -0,0 +1,35 @@
+use crate::response::{ErrorCode, ResponseError};
+
+/// Version of the protocol spoken by this build. Bumped whenever a change
+/// would break peers speaking an older version.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version still supported.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Features every server of this build supports, announced by HELLO.
+pub const FEATURES: [&str; 4] = ["pubsub", "cdc", "wait", "transactions"];
+
+/// Reply of the server to HELLO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    /// Version both peers speak from now on
+    pub protocol_version: u32,
+    pub server_version: String,
+    pub features: Vec<String>,
+}
+
+/// Picks the protocol version to speak with a peer that supports up to
+/// `version`, failing if it's too old.
+pub fn negotiate(version: u32) -> Result<u32, ResponseError> {
+    let negotiated = version.min(PROTOCOL_VERSION);
+    if negotiated < MIN_PROTOCOL_VERSION {
+        return Err(ResponseError::new(
+            ErrorCode::IncompatibleProtocol,
+            format!(
+                "protocol version {} isn't supported, the server speaks versions {} to {}",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        ));
+    }
+    Ok(negotiated)
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::pattern;
+
+/// Messages a subscriber can have pending before it's considered too slow
+/// and disconnected.
+pub const SUBSCRIBER_BUFFER_SIZE: usize = 1024;
+
+/// Prefix of the channels keyspace notifications are published to. It's
+/// followed by the key that changed, and the payload is the operation.
+pub const KEYSPACE_CHANNEL_PREFIX: &str = "__keyspace__:";
+
+/// Message published to a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub channel: String,
+    /// Pattern the subscriber matched the channel with, if it subscribed
+    /// through a pattern
+    pub pattern: Option<String>,
+    pub payload: String,
+}
+
+/**
+* Routes published messages to the subscribers of their channel, or of a
+* pattern matching it. Cloning a Broker returns a handle to the same
+* subscribers.
+*/
+#[derive(Clone, Default)]
+pub struct Broker {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    by_id: HashMap<u64, Subscriber>,
+}
+
+struct Subscriber {
+    channels: HashSet<String>,
+    patterns: HashSet<String>,
+    sender: mpsc::Sender<Message>,
+}
+
+/// Subscriptions of a single client. They're removed from the broker when
+/// it's dropped.
+pub struct Subscription {
+    id: u64,
+    broker: Broker,
+    receiver: mpsc::Receiver<Message>,
+}
+
+impl Broker {
+    pub fn new() -> Self {
+        Broker::default()
+    }
+
+    /// Registers a new subscriber, without any channels yet.
+    pub fn subscription(&self) -> Subscription {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let id = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.by_id.insert(
+            id,
+            Subscriber {
+                channels: HashSet::new(),
+                patterns: HashSet::new(),
+                sender,
+            },
+        );
+
+        Subscription {
+            id,
+            broker: self.clone(),
+            receiver,
+        }
+    }
+
+    /// Sends the message to every matching subscriber and returns how many
+    /// of them received it. Subscribers whose buffer is full are dropped.
+    pub fn publish(&self, channel: &str, payload: &str) -> usize {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let mut receivers = 0;
+        let mut lagging = Vec::new();
+
+        for (id, subscriber) in subscribers.by_id.iter() {
+            let mut messages = Vec::new();
+            if subscriber.channels.contains(channel) {
+                messages.push(Message {
+                    channel: channel.to_string(),
+                    pattern: None,
+                    payload: payload.to_string(),
+                });
+            }
+            for pattern in &subscriber.patterns {
+                if pattern::matches(pattern, channel) {
+                    messages.push(Message {
+                        channel: channel.to_string(),
+                        pattern: Some(pattern.clone()),
+                        payload: payload.to_string(),
+                    });
+                }
+            }
+
+            for message in messages {
+                match subscriber.sender.try_send(message) {
+                    Ok(_) => receivers += 1,
+                    Err(TrySendError::Full(_)) => {
+                        lagging.push(*id);
+                        break;
+                    }
+                    // The subscription is being dropped
+                    Err(TrySendError::Closed(_)) => break,
+                }
+            }
+        }
+
+        // Dropping the sender ends the subscription once the buffered
+        // messages are received, so the client gets disconnected
+        for id in lagging {
+            subscribers.by_id.remove(&id);
+        }
+        receivers
+    }
+
+    /// Publishes a keyspace notification of an operation on a key.
+    pub fn notify_keyspace(&self, operation: &str, key: &str) -> usize {
+        self.publish(&format!("{}{}", KEYSPACE_CHANNEL_PREFIX, key), operation)
+    }
+
+    fn update<F: FnOnce(&mut Subscriber)>(&self, id: u64, f: F) {
+        if let Some(subscriber) = self.subscribers.lock().unwrap().by_id.get_mut(&id) {
+            f(subscriber);
+        }
+    }
+}
+
+impl Subscription {
+    pub fn subscribe(&mut self, channels: &[String]) {
+        self.broker
+            .update(self.id, |s| s.channels.extend(channels.iter().cloned()));
+    }
+
+    /// Unsubscribes from the channels, or from all of them if none are given.
+    pub fn unsubscribe(&mut self, channels: &[String]) {
+        self.broker.update(self.id, |s| {
+            if channels.is_empty() {
+                s.channels.clear();
+            }
+            for channel in channels {
+                s.channels.remove(channel);
+            }
+        });
+    }
+
+    pub fn psubscribe(&mut self, patterns: &[String]) {
+        self.broker
+            .update(self.id, |s| s.patterns.extend(patterns.iter().cloned()));
+    }
+
+    /// Unsubscribes from the patterns, or from all of them if none are given.
+    pub fn punsubscribe(&mut self, patterns: &[String]) {
+        self.broker.update(self.id, |s| {
+            if patterns.is_empty() {
+                s.patterns.clear();
+            }
+            for pattern in patterns {
+                s.patterns.remove(pattern);
+            }
+        });
+    }
+
+    /// Number of channels and patterns subscribed to.
+    pub fn count(&self) -> usize {
+        let subscribers = self.broker.subscribers.lock().unwrap();
+        subscribers
+            .by_id
+            .get(&self.id)
+            .map(|s| s.channels.len() + s.patterns.len())
+            .unwrap_or(0)
+    }
+
+    /// Waits for the next message. Returns `None` if the subscriber was
+    /// dropped by the broker for not keeping up.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.broker
+            .subscribers
+            .lock()
+            .unwrap()
+            .by_id
+            .remove(&self.id);
+    }
+}
@@ -1,4 +1,14 @@
+use std::fmt;
+use std::string::FromUtf8Error;
+use std::time::Duration;
+
+use crate::info::Info;
 use crate::proto;
+use crate::proto::response::error::Code;
+use crate::protocol::Hello;
+use crate::pubsub::Message;
+use crate::slowlog::SlowLogEntry;
+use crate::storage::LogRecord;
 
 pub enum Status {
     Unespecified,
@@ -7,12 +17,253 @@ pub enum Status {
     Error,
 }
 
+/// Machine-readable reason of a failed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Unknown,
+    InvalidArgument,
+    KeyTooLarge,
+    ValueTooLarge,
+    Corruption,
+    Io,
+    Unauthorized,
+    Unsupported,
+    Internal,
+    TooManyClients,
+    RequestTooLarge,
+    Aborted,
+    IncompatibleProtocol,
+}
+
+/// Error returned to clients: a code they can match on plus a human-readable message.
+#[derive(Debug, Clone)]
+pub struct ResponseError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
 pub struct Response {
     pub status: Status,
     pub content: Option<String>,
+    pub error: Option<ResponseError>,
+    pub keys: Vec<String>,
+    /// Message pushed to a subscriber rather than a reply to a command
+    pub message: Option<Message>,
+    pub integer: Option<i64>,
+    /// Log records pushed to a CDC follower
+    pub changes: Vec<LogRecord>,
+    /// Responses of the commands run by EXEC
+    pub results: Vec<Response>,
+    pub hello: Option<Hello>,
+    pub info: Option<Info>,
+    /// Settings returned by CONFIG GET, as name and value
+    pub parameters: Vec<(String, String)>,
+    /// Entries returned by SLOWLOG GET, newest first
+    pub slowlog: Vec<SlowLogEntry>,
+}
+
+impl ErrorCode {
+    fn to_proto_code(self) -> Code {
+        match self {
+            ErrorCode::Unknown => Code::Unknown,
+            ErrorCode::InvalidArgument => Code::InvalidArgument,
+            ErrorCode::KeyTooLarge => Code::KeyTooLarge,
+            ErrorCode::ValueTooLarge => Code::ValueTooLarge,
+            ErrorCode::Corruption => Code::Corruption,
+            ErrorCode::Io => Code::Io,
+            ErrorCode::Unauthorized => Code::Unauthorized,
+            ErrorCode::Unsupported => Code::Unsupported,
+            ErrorCode::Internal => Code::Internal,
+            ErrorCode::TooManyClients => Code::TooManyClients,
+            ErrorCode::RequestTooLarge => Code::RequestTooLarge,
+            ErrorCode::Aborted => Code::Aborted,
+            ErrorCode::IncompatibleProtocol => Code::IncompatibleProtocol,
+        }
+    }
+
+    fn from_proto_code(code: Code) -> ErrorCode {
+        match code {
+            Code::Unknown => ErrorCode::Unknown,
+            Code::InvalidArgument => ErrorCode::InvalidArgument,
+            Code::KeyTooLarge => ErrorCode::KeyTooLarge,
+            Code::ValueTooLarge => ErrorCode::ValueTooLarge,
+            Code::Corruption => ErrorCode::Corruption,
+            Code::Io => ErrorCode::Io,
+            Code::Unauthorized => ErrorCode::Unauthorized,
+            Code::Unsupported => ErrorCode::Unsupported,
+            Code::Internal => ErrorCode::Internal,
+            Code::TooManyClients => ErrorCode::TooManyClients,
+            Code::RequestTooLarge => ErrorCode::RequestTooLarge,
+            Code::Aborted => ErrorCode::Aborted,
+            Code::IncompatibleProtocol => ErrorCode::IncompatibleProtocol,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_proto_code().as_str_name().fmt(f)
+    }
+}
+
+impl ResponseError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ResponseError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
+/// Classifies an internal error into the code reported to the client.
+impl From<&anyhow::Error> for ResponseError {
+    fn from(error: &anyhow::Error) -> Self {
+        if let Some(e) = error.downcast_ref::<ResponseError>() {
+            return e.clone();
+        }
+        if let Some(e) = error.downcast_ref::<std::io::Error>() {
+            return ResponseError::from(e);
+        }
+        if error.downcast_ref::<FromUtf8Error>().is_some() {
+            return ResponseError::new(ErrorCode::Corruption, error.to_string());
+        }
+        if error.downcast_ref::<prost::DecodeError>().is_some() {
+            return ResponseError::new(
+                ErrorCode::InvalidArgument,
+                format!("malformed command: {}", error),
+            );
+        }
+        ResponseError::new(ErrorCode::Internal, error.to_string())
+    }
+}
+
+impl From<&std::io::Error> for ResponseError {
+    fn from(error: &std::io::Error) -> Self {
+        if let Some(e) = error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<ResponseError>())
+        {
+            return e.clone();
+        }
+        match error.kind() {
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                ResponseError::new(ErrorCode::Corruption, error.to_string())
+            }
+            std::io::ErrorKind::InvalidInput => {
+                ResponseError::new(ErrorCode::InvalidArgument, error.to_string())
+            }
+            _ => ResponseError::new(ErrorCode::Io, error.to_string()),
+        }
+    }
 }
 
 impl Response {
+    fn new(status: Status) -> Response {
+        Response {
+            status,
+            content: None,
+            error: None,
+            keys: Vec::new(),
+            message: None,
+            integer: None,
+            changes: Vec::new(),
+            results: Vec::new(),
+            hello: None,
+            info: None,
+            parameters: Vec::new(),
+            slowlog: Vec::new(),
+        }
+    }
+
+    pub fn ok(content: Option<String>) -> Response {
+        Response {
+            content,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn keys(keys: Vec<String>) -> Response {
+        Response {
+            keys,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn integer(integer: i64) -> Response {
+        Response {
+            integer: Some(integer),
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn message(message: Message) -> Response {
+        Response {
+            message: Some(message),
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn changes(changes: Vec<LogRecord>) -> Response {
+        Response {
+            changes,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn results(results: Vec<Response>) -> Response {
+        Response {
+            results,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn hello(hello: Hello) -> Response {
+        Response {
+            hello: Some(hello),
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn info(info: Info) -> Response {
+        Response {
+            info: Some(info),
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn parameters(parameters: Vec<(String, String)>) -> Response {
+        Response {
+            parameters,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn slowlog(slowlog: Vec<SlowLogEntry>) -> Response {
+        Response {
+            slowlog,
+            ..Response::new(Status::Ok)
+        }
+    }
+
+    pub fn not_found() -> Response {
+        Response::new(Status::NotFound)
+    }
+
+    pub fn error(error: ResponseError) -> Response {
+        Response {
+            error: Some(error),
+            ..Response::new(Status::Error)
+        }
+    }
+
     pub fn to_proto_response(&self) -> proto::Response {
         let status = match self.status {
             Status::Unespecified => proto::response::Status::Unespecified,
@@ -23,27 +274,141 @@ impl Response {
         proto::Response {
             status: status as i32,
             content: self.content.clone(),
+            error: self.error.as_ref().map(|e| proto::response::Error {
+                code: e.code.to_proto_code() as i32,
+                message: e.message.clone(),
+            }),
+            keys: self.keys.clone(),
+            message: self.message.as_ref().map(|m| proto::response::Message {
+                channel: m.channel.clone(),
+                pattern: m.pattern.clone(),
+                payload: m.payload.clone(),
+            }),
+            integer: self.integer,
+            changes: self
+                .changes
+                .iter()
+                .map(|c| proto::response::Change {
+                    sequence: c.sequence,
+                    operation: match c.value {
+                        Some(_) => "set".to_string(),
+                        None => "del".to_string(),
+                    },
+                    key: c.key.clone(),
+                    value: c.value.clone(),
+                })
+                .collect(),
+            results: self.results.iter().map(|r| r.to_proto_response()).collect(),
+            hello: self.hello.as_ref().map(|h| proto::response::Hello {
+                protocol_version: h.protocol_version,
+                server_version: h.server_version.clone(),
+                features: h.features.clone(),
+            }),
+            info: self.info.as_ref().map(|i| proto::response::Info {
+                server_version: i.server_version.clone(),
+                uptime_secs: i.uptime_secs,
+                engine: i.engine.clone(),
+                file_size: i.file_size,
+                keys: i.keys,
+                connected_clients: i.connected_clients,
+                memory_bytes: i.memory_bytes,
+            }),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|(name, value)| proto::response::Parameter {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+            slowlog: self
+                .slowlog
+                .iter()
+                .map(|e| proto::response::SlowLogEntry {
+                    id: e.id,
+                    timestamp: e.timestamp,
+                    duration_micros: e.duration.as_micros() as u64,
+                    command: e.command.clone(),
+                    key: e.key.clone(),
+                    client: e.client.clone(),
+                })
+                .collect(),
         }
     }
 
     pub fn from_proto_response(proto_response: proto::Response) -> Response {
         match proto_response.status() {
             proto::response::Status::Unespecified => Response {
-                status: Status::Unespecified,
                 content: Some(proto_response.content().to_string()),
+                ..Response::new(Status::Unespecified)
             },
-            proto::response::Status::NotFound => Response {
-                status: Status::NotFound,
-                content: None,
-            },
+            proto::response::Status::NotFound => Response::not_found(),
             proto::response::Status::Ok => Response {
-                status: Status::Ok,
-                content: Some(proto_response.content().to_string()),
-            },
-            proto::response::Status::Error => Response {
-                status: Status::Error,
-                content: Some(proto_response.content().to_string()),
+                content: proto_response.content,
+                keys: proto_response.keys,
+                message: proto_response.message.map(|m| Message {
+                    channel: m.channel,
+                    pattern: m.pattern,
+                    payload: m.payload,
+                }),
+                integer: proto_response.integer,
+                changes: proto_response
+                    .changes
+                    .into_iter()
+                    .map(|c| LogRecord {
+                        sequence: c.sequence,
+                        key: c.key,
+                        value: c.value,
+                    })
+                    .collect(),
+                results: proto_response
+                    .results
+                    .into_iter()
+                    .map(Response::from_proto_response)
+                    .collect(),
+                hello: proto_response.hello.map(|h| Hello {
+                    protocol_version: h.protocol_version,
+                    server_version: h.server_version,
+                    features: h.features,
+                }),
+                info: proto_response.info.map(|i| Info {
+                    server_version: i.server_version,
+                    uptime_secs: i.uptime_secs,
+                    engine: i.engine,
+                    file_size: i.file_size,
+                    keys: i.keys,
+                    connected_clients: i.connected_clients,
+                    memory_bytes: i.memory_bytes,
+                }),
+                parameters: proto_response
+                    .parameters
+                    .into_iter()
+                    .map(|p| (p.name, p.value))
+                    .collect(),
+                slowlog: proto_response
+                    .slowlog
+                    .into_iter()
+                    .map(|e| SlowLogEntry {
+                        id: e.id,
+                        timestamp: e.timestamp,
+                        duration: Duration::from_micros(e.duration_micros),
+                        command: e.command,
+                        key: e.key,
+                        client: e.client,
+                    })
+                    .collect(),
+                ..Response::new(Status::Ok)
             },
+            proto::response::Status::Error => {
+                let error = match proto_response.error {
+                    Some(e) => ResponseError::new(ErrorCode::from_proto_code(e.code()), e.message),
+                    None => ResponseError::new(
+                        ErrorCode::Unknown,
+                        proto_response.content.unwrap_or_default(),
+                    ),
+                };
+                Response::error(error)
+            }
         }
     }
 }
@@ -21,7 +21,7 @@ impl CommandSerializer for ProtoCommandSerializer {
 
     fn decode(&self, bytes: &[u8]) -> Result<Command> {
         let proto_command = proto::Command::decode(bytes)?;
-        Ok(Command::from_proto_command(proto_command))
+        Ok(Command::from_proto_command(proto_command)?)
     }
 
     fn encoded_len(&self, command: &Command) -> usize {
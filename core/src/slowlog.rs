@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Command that took longer than the slow log threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowLogEntry {
+    /// Increases with every entry, so entries can be told apart after a reset
+    pub id: u64,
+    /// When the command finished, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub duration: Duration,
+    /// Name of the command, as typed in the CLI
+    pub command: String,
+    /// First key the command touched, if any. Values are never logged.
+    pub key: Option<String>,
+    /// Address of the client that sent the command
+    pub client: String,
+}
+
+/**
+* Log of the latest slow commands, kept in memory. Once it holds `max_len`
+* entries, recording a new one drops the oldest.
+*
+* Cloning it returns a handle to the same log.
+*/
+#[derive(Clone, Default)]
+pub struct SlowLog {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Newest first
+    entries: VecDeque<SlowLogEntry>,
+    next_id: u64,
+}
+
+impl SlowLog {
+    /// Records an entry, assigning its id. Nothing is kept if `max_len` is 0.
+    pub fn record(&self, mut entry: SlowLogEntry, max_len: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        entry.id = inner.next_id;
+        inner.next_id += 1;
+        inner.entries.push_front(entry);
+        inner.entries.truncate(max_len);
+    }
+
+    /// Returns up to `count` entries, newest first.
+    pub fn get(&self, count: usize) -> Vec<SlowLogEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner.entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every entry. Ids keep increasing.
+    pub fn reset(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+
+    /// Drops the oldest entries beyond `max_len`, after it was lowered.
+    pub fn truncate(&self, max_len: usize) {
+        self.inner.lock().unwrap().entries.truncate(max_len);
+    }
+}
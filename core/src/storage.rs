@@ -2,23 +2,69 @@ pub const ENCODING_VERSION: u8 = 1;
 pub const KEY_LENGTH_SIZE: usize = 1;
 pub const VALUE_LENGTH_SIZE: usize = 2;
 pub const TOMBSTONE_SIZE: usize = 1;
+pub const MAX_KEY_SIZE: usize = u8::MAX as usize;
+pub const MAX_VALUE_SIZE: usize = u16::MAX as usize;
 
-use std::collections::HashSet;
-use std::io::{Read, Seek};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufReader, Read, Seek};
 use std::sync::Arc;
 use std::{fs::File, fs::OpenOptions, io::Write, path::Path};
 
 use crate::index::{BinaryOffsetIndexer, OffsetIndexer};
+use crate::metrics::METRICS;
+use crate::response::{ErrorCode, ResponseError};
 use anyhow::Error;
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
 #[async_trait]
 pub trait Engine: Send {
+    /// Name of the engine, as reported by INFO.
+    fn name(&self) -> &'static str;
     async fn set(&mut self, key: &str, value: &str) -> std::io::Result<()>;
     async fn delete(&mut self, key: &str) -> std::io::Result<()>;
     async fn get(&mut self, key: &str) -> Result<Option<String>, Error>;
     async fn list(&mut self) -> anyhow::Result<HashSet<String>>;
+    /// Flushes pending writes and waits until they're persisted to disk.
+    async fn sync(&mut self) -> std::io::Result<()>;
+    /// Reads up to `max` records of the log, starting with the one at
+    /// `position` (0 is the start of the log). Returns no records when
+    /// `position` is the end of the log. `position` must be the sequence of
+    /// a record, see `check_log_position`.
+    async fn read_log(&mut self, position: u64, max: usize) -> anyhow::Result<Vec<LogRecord>>;
+    /// Fails with `InvalidInput` unless a record starts at `position`, or
+    /// it's the start or the end of the log. It reads the log up to `position`.
+    async fn check_log_position(&mut self, position: u64) -> anyhow::Result<()>;
+    /// Number of keys that are set, without reading the file.
+    fn key_count(&self) -> anyhow::Result<u64>;
+    /// Size of the database file in bytes.
+    async fn file_size(&mut self) -> std::io::Result<u64>;
+    /// Measures how the database file is used.
+    async fn stats(&mut self) -> anyhow::Result<StorageStats>;
+}
+
+/// Usage of the database file, as exported in the metrics.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    pub file_size: u64,
+    /// Bytes of the latest record of every key that's set
+    pub live_bytes: u64,
+    /// Bytes of the records that were overwritten or deleted, and of the
+    /// deletions themselves
+    pub dead_bytes: u64,
+    /// Keys whose offset is in the index
+    pub index_entries: u64,
+}
+
+/// Change recorded in the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Position of the log right after the record, where the next one starts.
+    /// It always increases, so it's used as the record's sequence number.
+    pub sequence: u64,
+    pub key: String,
+    /// New value of the key, `None` if it was deleted
+    pub value: Option<String>,
 }
 
 fn open_file(file_path: &str) -> Result<File, std::io::Error> {
@@ -33,7 +79,7 @@ fn open_file(file_path: &str) -> Result<File, std::io::Error> {
     let mut file = file_options.open(file_path)?;
 
     if !file_exists {
-        file.write(&[ENCODING_VERSION])?;
+        file.write_all(&[ENCODING_VERSION])?;
         // if let Err(e) = file.write(&[ENCODING_VERSION]) {
         //     panic!("Couldn't write to file: {}", e);
         // }
@@ -72,12 +118,20 @@ fn open_file(file_path: &str) -> Result<File, std::io::Error> {
 pub struct BinaryEngineV1 {
     file: Arc<Mutex<File>>,
     indexer: Box<dyn OffsetIndexer>,
+    /// Size of the latest record of every key that's set, measured when
+    /// the file is opened and kept up to date by the writes
+    live_sizes: HashMap<Vec<u8>, u64>,
+    /// Sum of `live_sizes`
+    live_bytes: u64,
 }
 
 /**
 * Factory method for Engine instances. It reads
 * the encoding version from the file (first byte) and
 * returns the appropriate Engine implementation.
+*
+* Files with an unknown version, or written by an engine that isn't
+* implemented yet (the LSM-tree), are rejected with `InvalidData`.
 */
 pub fn new_engine(file_path: &str) -> Result<Box<dyn Engine>, std::io::Error> {
     let mut file = open_file(file_path)?;
@@ -88,33 +142,101 @@ pub fn new_engine(file_path: &str) -> Result<Box<dyn Engine>, std::io::Error> {
 
     file.read_exact(&mut version)?;
 
-    let file = Arc::new(Mutex::new(open_file(file_path)?));
-    let indexer = Box::new(BinaryOffsetIndexer::new(file.clone()));
-
     match version[0] {
-        1 => Ok(Box::new(BinaryEngineV1 { file, indexer })),
-        2 => Ok(Box::new(LSMTreeEngine { _file: file })),
-        _ => panic!("Unsupported encoding version ({})", version[0]),
+        1 => Ok(Box::new(BinaryEngineV1::new(file_path)?)),
+        version => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unsupported encoding version ({})", version),
+        )),
     }
 }
 
 impl BinaryEngineV1 {
     pub fn new(file_path: &str) -> Result<Self, std::io::Error> {
-        let file = Arc::new(Mutex::new(open_file(file_path)?));
+        let file = open_file(file_path)?;
+        let live_sizes = live_sizes(&file)?;
+        let live_bytes = live_sizes.values().sum();
+        let file = Arc::new(Mutex::new(file));
         let indexer = Box::new(BinaryOffsetIndexer::new(file.clone()));
 
-        Ok(BinaryEngineV1 { file, indexer })
+        Ok(BinaryEngineV1 {
+            file,
+            indexer,
+            live_sizes,
+            live_bytes,
+        })
+    }
+
+    /// Records the size of the latest record of the key, `None` if it was deleted.
+    fn update_live_size(&mut self, key: &str, size: Option<u64>) {
+        let previous = match size {
+            Some(size) => {
+                self.live_bytes += size;
+                self.live_sizes.insert(key.as_bytes().to_vec(), size)
+            }
+            None => self.live_sizes.remove(key.as_bytes()),
+        };
+        self.live_bytes -= previous.unwrap_or(0);
     }
 }
 
+/// Reads the size of the latest record of every key that's set. A record
+/// cut short at the end of the file (by a crash while it was written) is
+/// left out, it counts as dead bytes.
+fn live_sizes(file: &File) -> std::io::Result<HashMap<Vec<u8>, u64>> {
+    let file_size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    // Skip encoding version byte
+    let mut position = reader.seek(std::io::SeekFrom::Start(1))?;
+
+    let mut live = HashMap::new();
+    while position < file_size {
+        let mut key_length_buffer = [0; KEY_LENGTH_SIZE];
+        let mut value_length_buffer = [0; VALUE_LENGTH_SIZE];
+        let mut tombstone = [0; TOMBSTONE_SIZE];
+
+        let record = reader.read_exact(&mut key_length_buffer).and_then(|_| {
+            let mut key: Vec<u8> = vec![0; key_length_buffer[0] as usize];
+            reader.read_exact(&mut key)?;
+            reader.read_exact(&mut value_length_buffer)?;
+            let value_length = u16::from_be_bytes(value_length_buffer) as usize;
+            // Seeking doesn't check the end of the file, the tombstone read does
+            reader.seek_relative(value_length as i64)?;
+            reader.read_exact(&mut tombstone)?;
+            Ok((key, value_length))
+        });
+        let (key, value_length) = match record {
+            Ok(record) => record,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+
+        let size = (KEY_LENGTH_SIZE + key.len() + VALUE_LENGTH_SIZE + value_length + TOMBSTONE_SIZE)
+            as u64;
+        position += size;
+        match tombstone[0] {
+            0 => live.insert(key, size),
+            _ => live.remove(&key),
+        };
+    }
+    METRICS.storage_read(position.min(file_size).saturating_sub(1));
+    Ok(live)
+}
+
 #[async_trait]
 impl Engine for BinaryEngineV1 {
+    fn name(&self) -> &'static str {
+        "binary-v1"
+    }
+
     async fn get(&mut self, key: &str) -> Result<Option<String>, Error> {
         let value = self.indexer.get(key).await?;
 
         if let Some(value) = value {
+            METRICS.index_hit();
             return Ok(Some(value));
         }
+        METRICS.index_miss();
 
         let mut value: Option<String> = None;
         self.file.lock().await.seek(std::io::SeekFrom::Start(1))?; // Skip encoding version byte
@@ -126,8 +248,7 @@ impl Engine for BinaryEngineV1 {
             let _ = self.file.lock().await.read_exact(&mut key_length_buffer);
             let key_length = key_length_buffer[0] as usize;
 
-            let mut current_key: Vec<u8> = Vec::with_capacity(key_length as usize);
-            current_key.resize(key_length as usize, 0);
+            let mut current_key: Vec<u8> = vec![0; key_length];
 
             let _ = self.file.lock().await.read_exact(&mut current_key);
 
@@ -137,8 +258,7 @@ impl Engine for BinaryEngineV1 {
             let _ = self.file.lock().await.read_exact(&mut value_length_buffer);
             let value_length = u16::from_be_bytes(value_length_buffer);
 
-            let mut current_value: Vec<u8> = Vec::with_capacity(value_length as usize);
-            current_value.resize(value_length as usize, 0);
+            let mut current_value: Vec<u8> = vec![0; value_length as usize];
 
             let _ = self.file.lock().await.read_exact(&mut current_value);
             let value_str = String::from_utf8(current_value)?;
@@ -154,6 +274,7 @@ impl Engine for BinaryEngineV1 {
                 }
             }
         }
+        METRICS.storage_read(file_size.saturating_sub(1));
         Ok(value)
     }
 
@@ -185,6 +306,8 @@ impl Engine for BinaryEngineV1 {
             .await;
 
         self.file.lock().await.write_all(&bytes)?;
+        METRICS.storage_written(bytes.len() as u64);
+        self.update_live_size(key, Some(bytes.len() as u64));
 
         Ok(())
     }
@@ -200,8 +323,7 @@ impl Engine for BinaryEngineV1 {
             let _ = self.file.lock().await.read_exact(&mut key_length_buffer);
             let key_length = key_length_buffer[0] as usize;
 
-            let mut current_key: Vec<u8> = Vec::with_capacity(key_length as usize);
-            current_key.resize(key_length as usize, 0);
+            let mut current_key: Vec<u8> = vec![0; key_length];
 
             let _ = self.file.lock().await.read_exact(&mut current_key);
 
@@ -211,8 +333,7 @@ impl Engine for BinaryEngineV1 {
             let _ = self.file.lock().await.read_exact(&mut value_length_buffer);
             let value_length = u16::from_be_bytes(value_length_buffer);
 
-            let mut current_value: Vec<u8> = Vec::with_capacity(value_length as usize);
-            current_value.resize(value_length as usize, 0);
+            let mut current_value: Vec<u8> = vec![0; value_length as usize];
 
             let _ = self.file.lock().await.read_exact(&mut current_value);
             let _ = String::from_utf8(current_value)?;
@@ -226,6 +347,7 @@ impl Engine for BinaryEngineV1 {
                 keys.remove(&current_key_str);
             }
         }
+        METRICS.storage_read(file_size.saturating_sub(1));
         Ok(keys)
     }
 
@@ -238,7 +360,7 @@ impl Engine for BinaryEngineV1 {
         bytes.extend_from_slice(key.as_bytes());
 
         // Add the length of the value (2 bytes, zero-padded)
-        let value_len = 1 as u16;
+        let value_len = 1_u16;
         bytes.push((value_len >> 8) as u8); // High byte
         bytes.push((value_len & 0xFF) as u8); // Low byte
 
@@ -249,9 +371,131 @@ impl Engine for BinaryEngineV1 {
         bytes.push(1);
 
         self.file.lock().await.write_all(&bytes)?;
+        METRICS.storage_written(bytes.len() as u64);
         self.indexer.delete(key).await;
+        self.update_live_size(key, None);
         Ok(())
     }
+
+    async fn sync(&mut self) -> std::io::Result<()> {
+        let mut file = self.file.lock().await;
+        file.flush()?;
+        file.sync_all()
+    }
+
+    async fn read_log(&mut self, position: u64, max: usize) -> anyhow::Result<Vec<LogRecord>> {
+        let file = self.file.lock().await;
+        let file_size = file.metadata()?.len();
+        // Skip encoding version byte
+        let mut position = position.max(1);
+        if position > file_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("position {} is past the end of the log", position),
+            )
+            .into());
+        }
+
+        let mut reader = BufReader::new(&*file);
+        reader.seek(std::io::SeekFrom::Start(position))?;
+
+        let start = position;
+        let mut records = Vec::new();
+        while position < file_size && records.len() < max {
+            let mut key_length_buffer = [0; KEY_LENGTH_SIZE];
+            reader.read_exact(&mut key_length_buffer)?;
+            let key_length = key_length_buffer[0] as usize;
+
+            let mut key: Vec<u8> = vec![0; key_length];
+            reader.read_exact(&mut key)?;
+
+            let mut value_length_buffer = [0; VALUE_LENGTH_SIZE];
+            reader.read_exact(&mut value_length_buffer)?;
+            let value_length = u16::from_be_bytes(value_length_buffer) as usize;
+
+            let mut value: Vec<u8> = vec![0; value_length];
+            reader.read_exact(&mut value)?;
+
+            let mut tombstone = [0; TOMBSTONE_SIZE];
+            reader.read_exact(&mut tombstone)?;
+
+            position +=
+                (KEY_LENGTH_SIZE + key_length + VALUE_LENGTH_SIZE + value_length + TOMBSTONE_SIZE)
+                    as u64;
+            records.push(LogRecord {
+                sequence: position,
+                key: String::from_utf8(key)?,
+                value: match tombstone[0] {
+                    0 => Some(String::from_utf8(value)?),
+                    _ => None,
+                },
+            });
+        }
+        METRICS.storage_read(position - start);
+        Ok(records)
+    }
+
+    async fn check_log_position(&mut self, position: u64) -> anyhow::Result<()> {
+        let file = self.file.lock().await;
+        let file_size = file.metadata()?.len();
+        if position > file_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("position {} is past the end of the log", position),
+            )
+            .into());
+        }
+
+        let mut reader = BufReader::new(&*file);
+        // Skip encoding version byte
+        let mut current = reader.seek(std::io::SeekFrom::Start(1))?;
+        // Only the lengths are read, the keys and values are skipped
+        while current < position {
+            let mut key_length_buffer = [0; KEY_LENGTH_SIZE];
+            reader.read_exact(&mut key_length_buffer)?;
+            let key_length = key_length_buffer[0] as usize;
+            reader.seek_relative(key_length as i64)?;
+
+            let mut value_length_buffer = [0; VALUE_LENGTH_SIZE];
+            reader.read_exact(&mut value_length_buffer)?;
+            let value_length = u16::from_be_bytes(value_length_buffer) as usize;
+            reader.seek_relative((value_length + TOMBSTONE_SIZE) as i64)?;
+
+            current +=
+                (KEY_LENGTH_SIZE + key_length + VALUE_LENGTH_SIZE + value_length + TOMBSTONE_SIZE)
+                    as u64;
+        }
+        METRICS.storage_read(current.saturating_sub(1));
+
+        if current != position.max(1) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("position {} isn't the start of a record", position),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn key_count(&self) -> anyhow::Result<u64> {
+        Ok(self.live_sizes.len() as u64)
+    }
+
+    async fn file_size(&mut self) -> std::io::Result<u64> {
+        Ok(self.file.lock().await.metadata()?.len())
+    }
+
+    async fn stats(&mut self) -> anyhow::Result<StorageStats> {
+        let file_size = self.file.lock().await.metadata()?.len();
+        Ok(StorageStats {
+            file_size,
+            live_bytes: self.live_bytes,
+            // Besides the overwritten and deleted records, it covers a
+            // record cut short at the end of the file
+            dead_bytes: file_size.saturating_sub(1 + self.live_bytes),
+            index_entries: self.indexer.entries() as u64,
+        })
+    }
 }
 
 /**
@@ -271,19 +515,201 @@ impl LSMTreeEngine {
 
 #[async_trait]
 impl Engine for LSMTreeEngine {
+    fn name(&self) -> &'static str {
+        "lsm-tree"
+    }
+
     async fn get(&mut self, _key: &str) -> Result<Option<String>, Error> {
-        unimplemented!()
+        Err(unsupported().into())
     }
 
     async fn set(&mut self, _key: &str, _value: &str) -> std::io::Result<()> {
-        unimplemented!()
+        Err(unsupported_io())
     }
 
     async fn list(&mut self) -> anyhow::Result<HashSet<String>> {
-        unimplemented!()
+        Err(unsupported().into())
     }
 
     async fn delete(&mut self, _key: &str) -> std::io::Result<()> {
-        unimplemented!()
+        Err(unsupported_io())
+    }
+
+    async fn sync(&mut self) -> std::io::Result<()> {
+        Err(unsupported_io())
+    }
+
+    async fn read_log(&mut self, _position: u64, _max: usize) -> anyhow::Result<Vec<LogRecord>> {
+        Err(unsupported().into())
+    }
+
+    async fn check_log_position(&mut self, _position: u64) -> anyhow::Result<()> {
+        Err(unsupported().into())
+    }
+
+    fn key_count(&self) -> anyhow::Result<u64> {
+        Err(unsupported().into())
+    }
+
+    async fn file_size(&mut self) -> std::io::Result<u64> {
+        Err(unsupported_io())
+    }
+
+    async fn stats(&mut self) -> anyhow::Result<StorageStats> {
+        Err(unsupported().into())
+    }
+}
+
+/// Error of the operations of the LSM-tree engine, which isn't implemented
+/// yet, so clients get an error instead of crashing the server.
+fn unsupported() -> ResponseError {
+    ResponseError::new(
+        ErrorCode::Unsupported,
+        "the lsm-tree engine isn't implemented yet",
+    )
+}
+
+/// `unsupported` for the operations returning an `io::Error`, which keeps
+/// its code when it's sent to the client.
+fn unsupported_io() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, unsupported())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Database file of a test, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let path =
+                std::env::temp_dir().join(format!("tuna-{}-{}.db", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn code(error: &anyhow::Error) -> ErrorCode {
+        ResponseError::from(error).code
+    }
+
+    /// Kind of the I/O error behind `error`.
+    fn kind(error: &anyhow::Error) -> std::io::ErrorKind {
+        error.downcast_ref::<std::io::Error>().unwrap().kind()
+    }
+
+    /// Engine whose log has the record of `key` = `value`, from byte 1 to
+    /// 13, then the deletion of `key`, from byte 13 to 21.
+    async fn engine_with_records(file: &TempFile) -> BinaryEngineV1 {
+        let mut engine = BinaryEngineV1::new(file.path()).unwrap();
+        engine.set("key", "value").await.unwrap();
+        engine.delete("key").await.unwrap();
+        engine
+    }
+
+    #[tokio::test]
+    async fn log_starts_at_0_when_it_is_empty() {
+        let file = TempFile::new("empty-log");
+        let mut engine = BinaryEngineV1::new(file.path()).unwrap();
+
+        engine.check_log_position(0).await.unwrap();
+        assert!(engine.read_log(0, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn log_is_read_from_the_start_of_any_record() {
+        let file = TempFile::new("log-records");
+        let mut engine = engine_with_records(&file).await;
+
+        engine.check_log_position(0).await.unwrap();
+        let records = engine.read_log(0, 10).await.unwrap();
+        assert_eq!(
+            records,
+            [
+                LogRecord {
+                    sequence: 13,
+                    key: "key".to_string(),
+                    value: Some("value".to_string()),
+                },
+                LogRecord {
+                    sequence: 21,
+                    key: "key".to_string(),
+                    value: None,
+                },
+            ]
+        );
+
+        engine.check_log_position(13).await.unwrap();
+        assert_eq!(engine.read_log(13, 10).await.unwrap(), records[1..]);
+        assert_eq!(engine.read_log(0, 1).await.unwrap(), records[..1]);
+    }
+
+    #[tokio::test]
+    async fn log_is_empty_from_its_end() {
+        let file = TempFile::new("log-end");
+        let mut engine = engine_with_records(&file).await;
+
+        engine.check_log_position(21).await.unwrap();
+        assert!(engine.read_log(21, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn log_positions_in_the_middle_of_a_record_are_rejected() {
+        let file = TempFile::new("log-mid-record");
+        let mut engine = engine_with_records(&file).await;
+
+        for position in [5, 14] {
+            let error = engine.check_log_position(position).await.unwrap_err();
+            assert_eq!(kind(&error), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[tokio::test]
+    async fn log_positions_past_the_end_are_rejected() {
+        let file = TempFile::new("log-past-end");
+        let mut engine = engine_with_records(&file).await;
+
+        let error = engine.check_log_position(22).await.unwrap_err();
+        assert_eq!(kind(&error), std::io::ErrorKind::InvalidInput);
+        let error = engine.read_log(22, 10).await.unwrap_err();
+        assert_eq!(kind(&error), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn lsm_tree_engine_returns_unsupported_errors() {
+        let file = TempFile::new("lsm-tree");
+        let mut engine = LSMTreeEngine::new(file.path()).unwrap();
+
+        assert_eq!(
+            code(&engine.get("key").await.unwrap_err()),
+            ErrorCode::Unsupported
+        );
+        let set = engine.set("key", "value").await.unwrap_err();
+        assert_eq!(ResponseError::from(&set).code, ErrorCode::Unsupported);
+        assert_eq!(
+            code(&engine.list().await.unwrap_err()),
+            ErrorCode::Unsupported
+        );
+        assert_eq!(
+            code(&engine.read_log(0, 10).await.unwrap_err()),
+            ErrorCode::Unsupported
+        );
+        assert_eq!(
+            code(&engine.key_count().unwrap_err()),
+            ErrorCode::Unsupported
+        );
     }
 }
@@ -0,0 +1,118 @@
+use tokio::sync::oneshot::error::TryRecvError;
+
+use crate::command::{self, Command, Context};
+use crate::response::{ErrorCode, Response, ResponseError};
+use crate::wait::Waiter;
+
+/**
+* Transaction of a single connection: the commands queued between MULTI and
+* EXEC, and the keys watched with WATCH.
+*
+* EXEC runs the queued commands while holding the engine, so no other
+* client's command can run in between. It's aborted without running any of
+* them if a watched key was written since it was watched.
+*/
+#[derive(Default)]
+pub struct Transaction {
+    queued: Option<Vec<Command>>,
+    /// Woken up by the first write of each watched key
+    watched: Vec<Waiter>,
+}
+
+impl Transaction {
+    /// Whether MULTI was called, so commands are queued instead of run.
+    pub fn is_active(&self) -> bool {
+        self.queued.is_some()
+    }
+
+    /// Commands queued since MULTI, none if there's no transaction.
+    pub fn queued(&self) -> &[Command] {
+        self.queued.as_deref().unwrap_or_default()
+    }
+
+    pub fn multi(&mut self) -> Response {
+        if self.is_active() {
+            return misuse("MULTI calls can't be nested");
+        }
+        self.queued = Some(Vec::new());
+        Response::ok(None)
+    }
+
+    /// Queues a command to run on EXEC. Commands that block or stream can't
+    /// be queued.
+    pub fn queue(&mut self, command: Command) -> Response {
+        let Some(queued) = &mut self.queued else {
+            return misuse("commands can only be queued after MULTI");
+        };
+        if let Err(e) = command.validate() {
+            return Response::error(e);
+        }
+        match command {
+            Command::Get { .. }
+            | Command::Set { .. }
+            | Command::Del { .. }
+            | Command::List
+            | Command::Dbsize
+            | Command::Flushdb
+            | Command::Ping { .. }
+            | Command::Publish { .. } => {
+                queued.push(command);
+                Response::ok(Some("QUEUED".to_string()))
+            }
+            command => misuse(&format!("{} can't run in a transaction", command.name())),
+        }
+    }
+
+    pub async fn exec(&mut self, ctx: &Context) -> Response {
+        let Some(commands) = self.queued.take() else {
+            return misuse("EXEC without MULTI");
+        };
+        let watched = std::mem::take(&mut self.watched);
+
+        let mut engine = ctx.engine.lock().await;
+        // Writes wake up the watchers while holding the engine, so watched
+        // keys can't change between this check and the commands below
+        let changed = watched
+            .into_iter()
+            .any(|mut w| !matches!(w.try_recv(), Err(TryRecvError::Empty)));
+        if changed {
+            return Response::error(ResponseError::new(
+                ErrorCode::Aborted,
+                "transaction aborted, a watched key changed",
+            ));
+        }
+
+        let mut results = Vec::with_capacity(commands.len());
+        for command in commands {
+            results.push(command::run_on_engine(ctx, engine.as_mut(), command).await);
+        }
+        Response::results(results)
+    }
+
+    /// Drops the queued commands and the watched keys.
+    pub fn discard(&mut self) -> Response {
+        if self.queued.take().is_none() {
+            return misuse("DISCARD without MULTI");
+        }
+        self.watched.clear();
+        Response::ok(None)
+    }
+
+    pub fn watch(&mut self, ctx: &Context, keys: &[String]) -> Response {
+        if self.is_active() {
+            return misuse("WATCH isn't allowed after MULTI");
+        }
+        self.watched
+            .extend(keys.iter().map(|key| ctx.waiters.register(key)));
+        Response::ok(None)
+    }
+
+    pub fn unwatch(&mut self) -> Response {
+        self.watched.clear();
+        Response::ok(None)
+    }
+}
+
+fn misuse(message: &str) -> Response {
+    Response::error(ResponseError::new(ErrorCode::InvalidArgument, message))
+}
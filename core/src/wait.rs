@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::oneshot::{self, error::RecvError, error::TryRecvError};
+
+/// Receives the new value of the key, `None` if it was deleted.
+type Sender = oneshot::Sender<Option<String>>;
+
+/**
+* Clients waiting for a key to be written, blocked in WAIT or watching it
+* for a transaction. They're woken up with the new value of the key (`None`
+* if it was deleted) by the write path.
+*/
+#[derive(Clone, Default)]
+pub struct Waiters {
+    by_key: Arc<Mutex<HashMap<String, Vec<Sender>>>>,
+}
+
+/// Client waiting for the next write of a key. Awaiting it returns the new
+/// value. Dropping it, once woken up or after giving up, unregisters it.
+pub struct Waiter {
+    key: String,
+    waiters: Waiters,
+    receiver: oneshot::Receiver<Option<String>>,
+}
+
+impl Waiters {
+    /// Registers a waiter for the next write of the key.
+    pub fn register(&self, key: &str) -> Waiter {
+        let (sender, receiver) = oneshot::channel();
+        self.by_key
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push(sender);
+        Waiter {
+            key: key.to_string(),
+            waiters: self.clone(),
+            receiver,
+        }
+    }
+
+    /// Wakes up every waiter of the key with its new value.
+    pub fn wake(&self, key: &str, value: Option<&str>) {
+        let Some(waiters) = self.by_key.lock().unwrap().remove(key) else {
+            return;
+        };
+        for waiter in waiters {
+            let _ = waiter.send(value.map(|value| value.to_string()));
+        }
+    }
+}
+
+impl Waiter {
+    /// Returns the new value if the key was written, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<String>, TryRecvError> {
+        self.receiver.try_recv()
+    }
+}
+
+impl Future for Waiter {
+    type Output = Result<Option<String>, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx)
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // Closed first, so it's dropped along with the other waiters that gave up
+        self.receiver.close();
+        let mut by_key = self.waiters.by_key.lock().unwrap();
+        if let Some(senders) = by_key.get_mut(&self.key) {
+            senders.retain(|sender| !sender.is_closed());
+            if senders.is_empty() {
+                by_key.remove(&self.key);
+            }
+        }
+    }
+}
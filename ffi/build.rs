@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Generates the C header from the public functions of the crate, in the
+/// build directory. `include/tuna.h` is the committed copy, which the tests
+/// check against the generated one.
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Unable to generate the C header")
+        .write_to_file(out_dir.join("tuna.h"));
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,147 @@
+/* Generated by cbindgen from ffi/src/lib.rs, don't edit by hand. */
+
+#ifndef TUNA_H
+#define TUNA_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum TunaStatus {
+  TUNA_STATUS_OK = 0,
+  /**
+   * The key doesn't exist, or the iterator is exhausted
+   */
+  TUNA_STATUS_NOT_FOUND = 1,
+  /**
+   * The error out parameter, if any, describes the error
+   */
+  TUNA_STATUS_ERROR = 2,
+} TunaStatus;
+
+/**
+ * Database opened in-process.
+ */
+typedef struct TunaDb TunaDb;
+
+/**
+ * Iterator over a snapshot of the key-value pairs, sorted by key.
+ */
+typedef struct TunaIter TunaIter;
+
+/**
+ * Settings of a database, see `tuna_options_default`.
+ */
+typedef struct TunaOptions {
+  /**
+   * Creates the database file if it doesn't exist yet
+   */
+  bool create_if_missing;
+  /**
+   * Waits for every write to reach the disk before returning
+   */
+  bool sync_writes;
+} TunaOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the default settings.
+ */
+struct TunaOptions tuna_options_default(void);
+
+/**
+ * Opens the database file at `path`, with the default settings if
+ * `options` is NULL. Returns NULL and sets `*error` on failure.
+ *
+ * # Safety
+ * `path` must be a valid C string, `options` NULL or a valid pointer and
+ * `error` NULL or a valid pointer.
+ */
+struct TunaDb *tuna_open(const char *path, const struct TunaOptions *options, char **error);
+
+/**
+ * Persists the pending writes, closes the database and releases the
+ * handle, even if it fails. Does nothing if `db` is NULL.
+ *
+ * # Safety
+ * `db` must be NULL or a handle returned by `tuna_open` that isn't used
+ * afterwards, and `error` NULL or a valid pointer.
+ */
+enum TunaStatus tuna_close(struct TunaDb *db, char **error);
+
+/**
+ * Sets `*value` to the value of the key, or returns `TUNA_STATUS_NOT_FOUND`
+ * if it doesn't exist. The value must be released with `tuna_free`.
+ *
+ * # Safety
+ * `db` must be a handle returned by `tuna_open`, `key` a valid C string,
+ * `value` a valid pointer and `error` NULL or a valid pointer.
+ */
+enum TunaStatus tuna_get(const struct TunaDb *db, const char *key, char **value, char **error);
+
+/**
+ * # Safety
+ * `db` must be a handle returned by `tuna_open`, `key` and `value` valid C
+ * strings and `error` NULL or a valid pointer.
+ */
+enum TunaStatus tuna_set(const struct TunaDb *db, const char *key, const char *value, char **error);
+
+/**
+ * Deletes the key. Deleting a key that doesn't exist isn't an error.
+ *
+ * # Safety
+ * `db` must be a handle returned by `tuna_open`, `key` a valid C string
+ * and `error` NULL or a valid pointer.
+ */
+enum TunaStatus tuna_delete(const struct TunaDb *db, const char *key, char **error);
+
+/**
+ * Returns an iterator over the key-value pairs as they are when it's
+ * called, to release with `tuna_iter_free`. Returns NULL and sets
+ * `*error` on failure.
+ *
+ * # Safety
+ * `db` must be a handle returned by `tuna_open` and `error` NULL or a
+ * valid pointer.
+ */
+struct TunaIter *tuna_iter(const struct TunaDb *db, char **error);
+
+/**
+ * Sets `*key` and `*value` to the next pair, or returns
+ * `TUNA_STATUS_NOT_FOUND` once the iterator is exhausted. Both must be
+ * released with `tuna_free`.
+ *
+ * # Safety
+ * `iter` must be an iterator returned by `tuna_iter`, `key` and `value`
+ * valid pointers and `error` NULL or a valid pointer.
+ */
+enum TunaStatus tuna_iter_next(struct TunaIter *iter, char **key, char **value, char **error);
+
+/**
+ * Releases an iterator. Does nothing if `iter` is NULL.
+ *
+ * # Safety
+ * `iter` must be NULL or an iterator returned by `tuna_iter` that isn't
+ * used afterwards.
+ */
+void tuna_iter_free(struct TunaIter *iter);
+
+/**
+ * Releases a string returned by the library. Does nothing if `s` is NULL.
+ *
+ * # Safety
+ * `s` must be NULL or a string returned by the library that isn't used
+ * afterwards.
+ */
+void tuna_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TUNA_H */
@@ -0,0 +1,321 @@
+/*!
+* C bindings for the embedded database, `core::db::Db`. The header is
+* generated by the build script, and a copy is kept in `include/tuna.h`.
+*
+* Ownership rules:
+* - Handles returned by `tuna_open` and `tuna_iter` are owned by the caller,
+*   who releases them with `tuna_close` and `tuna_iter_free`.
+* - Strings returned through out parameters (values, keys and errors) are
+*   allocated by the library and owned by the caller, who releases them with
+*   `tuna_free`. They must not be released with `free`.
+* - Strings passed as arguments are borrowed for the duration of the call.
+*
+* A panic never unwinds into the caller: the function fails as it would on
+* any other error instead.
+*/
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use core::db::{Db, DbOptions, Iter};
+
+/// Database opened in-process.
+pub struct TunaDb {
+    db: Db,
+}
+
+/// Iterator over a snapshot of the key-value pairs, sorted by key.
+pub struct TunaIter {
+    iter: Iter,
+}
+
+/// Settings of a database, see `tuna_options_default`.
+#[repr(C)]
+pub struct TunaOptions {
+    /// Creates the database file if it doesn't exist yet
+    pub create_if_missing: bool,
+    /// Waits for every write to reach the disk before returning
+    pub sync_writes: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum TunaStatus {
+    Ok = 0,
+    /// The key doesn't exist, or the iterator is exhausted
+    NotFound = 1,
+    /// The error out parameter, if any, describes the error
+    Error = 2,
+}
+
+/// Returns the default settings.
+#[no_mangle]
+pub extern "C" fn tuna_options_default() -> TunaOptions {
+    let options = DbOptions::default();
+    TunaOptions {
+        create_if_missing: options.create_if_missing,
+        sync_writes: options.sync_writes,
+    }
+}
+
+/// Opens the database file at `path`, with the default settings if
+/// `options` is NULL. Returns NULL and sets `*error` on failure.
+///
+/// # Safety
+/// `path` must be a valid C string, `options` NULL or a valid pointer and
+/// `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_open(
+    path: *const c_char,
+    options: *const TunaOptions,
+    error: *mut *mut c_char,
+) -> *mut TunaDb {
+    guard(error, ptr::null_mut(), || {
+        let result = str_arg(path, "path").and_then(|path| {
+            let options = match options.as_ref() {
+                Some(options) => DbOptions {
+                    create_if_missing: options.create_if_missing,
+                    sync_writes: options.sync_writes,
+                },
+                None => DbOptions::default(),
+            };
+            Db::open(path, options)
+        });
+        match result {
+            Ok(db) => Box::into_raw(Box::new(TunaDb { db })),
+            Err(e) => {
+                set_error(error, e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Persists the pending writes, closes the database and releases the
+/// handle, even if it fails. Does nothing if `db` is NULL.
+///
+/// # Safety
+/// `db` must be NULL or a handle returned by `tuna_open` that isn't used
+/// afterwards, and `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_close(db: *mut TunaDb, error: *mut *mut c_char) -> TunaStatus {
+    guard(error, TunaStatus::Error, || {
+        if db.is_null() {
+            return TunaStatus::Ok;
+        }
+        let db = Box::from_raw(db);
+        status(db.db.close(), error)
+    })
+}
+
+/// Sets `*value` to the value of the key, or returns `TUNA_STATUS_NOT_FOUND`
+/// if it doesn't exist. The value must be released with `tuna_free`.
+///
+/// # Safety
+/// `db` must be a handle returned by `tuna_open`, `key` a valid C string,
+/// `value` a valid pointer and `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_get(
+    db: *const TunaDb,
+    key: *const c_char,
+    value: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> TunaStatus {
+    guard(error, TunaStatus::Error, || {
+        let result = db_arg(db).and_then(|db| db.get(str_arg(key, "key")?));
+        match result.and_then(|found| found.map(into_c_string).transpose()) {
+            Ok(Some(found)) => {
+                *value = found;
+                TunaStatus::Ok
+            }
+            Ok(None) => TunaStatus::NotFound,
+            Err(e) => {
+                set_error(error, e);
+                TunaStatus::Error
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `db` must be a handle returned by `tuna_open`, `key` and `value` valid C
+/// strings and `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_set(
+    db: *const TunaDb,
+    key: *const c_char,
+    value: *const c_char,
+    error: *mut *mut c_char,
+) -> TunaStatus {
+    guard(error, TunaStatus::Error, || {
+        let result =
+            db_arg(db).and_then(|db| db.set(str_arg(key, "key")?, str_arg(value, "value")?));
+        status(result, error)
+    })
+}
+
+/// Deletes the key. Deleting a key that doesn't exist isn't an error.
+///
+/// # Safety
+/// `db` must be a handle returned by `tuna_open`, `key` a valid C string
+/// and `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_delete(
+    db: *const TunaDb,
+    key: *const c_char,
+    error: *mut *mut c_char,
+) -> TunaStatus {
+    guard(error, TunaStatus::Error, || {
+        let result = db_arg(db).and_then(|db| db.delete(str_arg(key, "key")?));
+        status(result, error)
+    })
+}
+
+/// Returns an iterator over the key-value pairs as they are when it's
+/// called, to release with `tuna_iter_free`. Returns NULL and sets
+/// `*error` on failure.
+///
+/// # Safety
+/// `db` must be a handle returned by `tuna_open` and `error` NULL or a
+/// valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_iter(db: *const TunaDb, error: *mut *mut c_char) -> *mut TunaIter {
+    guard(error, ptr::null_mut(), || {
+        match db_arg(db).and_then(|db| db.iter()) {
+            Ok(iter) => Box::into_raw(Box::new(TunaIter { iter })),
+            Err(e) => {
+                set_error(error, e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Sets `*key` and `*value` to the next pair, or returns
+/// `TUNA_STATUS_NOT_FOUND` once the iterator is exhausted. Both must be
+/// released with `tuna_free`.
+///
+/// # Safety
+/// `iter` must be an iterator returned by `tuna_iter`, `key` and `value`
+/// valid pointers and `error` NULL or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_iter_next(
+    iter: *mut TunaIter,
+    key: *mut *mut c_char,
+    value: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> TunaStatus {
+    guard(error, TunaStatus::Error, || {
+        let Some(iter) = iter.as_mut() else {
+            set_error(error, anyhow::anyhow!("iterator is NULL"));
+            return TunaStatus::Error;
+        };
+        let Some((next_key, next_value)) = iter.iter.next() else {
+            return TunaStatus::NotFound;
+        };
+        let result = into_c_string(next_key).and_then(|next_key| {
+            into_c_string(next_value)
+                .map(|next_value| (next_key, next_value))
+                .inspect_err(|_| tuna_free(next_key))
+        });
+        match result {
+            Ok((next_key, next_value)) => {
+                *key = next_key;
+                *value = next_value;
+                TunaStatus::Ok
+            }
+            Err(e) => {
+                set_error(error, e);
+                TunaStatus::Error
+            }
+        }
+    })
+}
+
+/// Releases an iterator. Does nothing if `iter` is NULL.
+///
+/// # Safety
+/// `iter` must be NULL or an iterator returned by `tuna_iter` that isn't
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_iter_free(iter: *mut TunaIter) {
+    guard(ptr::null_mut(), (), || {
+        if !iter.is_null() {
+            drop(Box::from_raw(iter));
+        }
+    })
+}
+
+/// Releases a string returned by the library. Does nothing if `s` is NULL.
+///
+/// # Safety
+/// `s` must be NULL or a string returned by the library that isn't used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tuna_free(s: *mut c_char) {
+    guard(ptr::null_mut(), (), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+unsafe fn db_arg<'a>(db: *const TunaDb) -> anyhow::Result<&'a Db> {
+    db.as_ref()
+        .map(|db| &db.db)
+        .ok_or(anyhow::anyhow!("database is NULL"))
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    if s.is_null() {
+        return Err(anyhow::anyhow!("{} is NULL", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| anyhow::anyhow!("{} isn't valid UTF-8", name))
+}
+
+fn into_c_string(s: String) -> anyhow::Result<*mut c_char> {
+    // Values written through the server may contain NUL bytes
+    CString::new(s)
+        .map(CString::into_raw)
+        .map_err(|_| anyhow::anyhow!("value contains a NUL byte"))
+}
+
+unsafe fn status(result: anyhow::Result<()>, error: *mut *mut c_char) -> TunaStatus {
+    match result {
+        Ok(()) => TunaStatus::Ok,
+        Err(e) => {
+            set_error(error, e);
+            TunaStatus::Error
+        }
+    }
+}
+
+/// Stores the error message in `*error`, unless the caller isn't interested.
+unsafe fn set_error(error: *mut *mut c_char, e: anyhow::Error) {
+    if error.is_null() {
+        return;
+    }
+    let message = e.to_string().replace('\0', "");
+    *error = CString::new(message)
+        .expect("NUL bytes were removed")
+        .into_raw();
+}
+
+/// Runs the body of an entry point, returning `on_panic` and setting
+/// `*error` if it panics. Unwinding into C would abort the process.
+unsafe fn guard<T>(error: *mut *mut c_char, on_panic: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_error(error, anyhow::anyhow!("internal error: {}", message));
+            on_panic
+        }
+    }
+}
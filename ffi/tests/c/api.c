@@ -0,0 +1,107 @@
+/* Exercises the C API, see tests/c_api.rs. Exits with 1 on failure. */
+#include <stdio.h>
+#include <string.h>
+
+#include "tuna.h"
+
+#define CHECK(condition)                                                    \
+    do {                                                                    \
+        if (!(condition)) {                                                 \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                            \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+static int test_missing_file(const char *path) {
+    TunaOptions options = tuna_options_default();
+    options.create_if_missing = false;
+    char *error = NULL;
+
+    CHECK(tuna_open(path, &options, &error) == NULL);
+    CHECK(error != NULL && strstr(error, "doesn't exist") != NULL);
+    tuna_free(error);
+    return 0;
+}
+
+static int test_unsupported_file(const char *path) {
+    char bad_path[4096];
+    char *error = NULL;
+    snprintf(bad_path, sizeof(bad_path), "%s.bad", path);
+    /* A single byte with an encoding version the library doesn't know */
+    FILE *file = fopen(bad_path, "wb");
+    CHECK(file != NULL);
+    CHECK(fputc(0x07, file) == 0x07);
+    CHECK(fclose(file) == 0);
+
+    CHECK(tuna_open(bad_path, NULL, &error) == NULL);
+    CHECK(error != NULL && strstr(error, "unsupported encoding version") != NULL);
+    tuna_free(error);
+    remove(bad_path);
+    return 0;
+}
+
+static int test_read_write(const char *path) {
+    char *error = NULL;
+    char *value = NULL;
+    TunaDb *db = tuna_open(path, NULL, &error);
+    CHECK(db != NULL && error == NULL);
+
+    CHECK(tuna_set(db, "fish", "tuna", &error) == TUNA_STATUS_OK);
+    CHECK(tuna_set(db, "bird", "heron", &error) == TUNA_STATUS_OK);
+    CHECK(tuna_set(db, "cat", "tabby", NULL) == TUNA_STATUS_OK);
+    CHECK(tuna_delete(db, "cat", &error) == TUNA_STATUS_OK);
+
+    CHECK(tuna_get(db, "fish", &value, &error) == TUNA_STATUS_OK);
+    CHECK(strcmp(value, "tuna") == 0);
+    tuna_free(value);
+    CHECK(tuna_get(db, "cat", &value, &error) == TUNA_STATUS_NOT_FOUND);
+
+    CHECK(tuna_set(db, "", "empty", &error) == TUNA_STATUS_ERROR);
+    CHECK(error != NULL);
+    tuna_free(error);
+    error = NULL;
+
+    CHECK(tuna_close(db, &error) == TUNA_STATUS_OK);
+    return 0;
+}
+
+static int test_iterate(const char *path) {
+    char *error = NULL;
+    char *key = NULL;
+    char *value = NULL;
+    TunaDb *db = tuna_open(path, NULL, &error);
+    CHECK(db != NULL);
+
+    TunaIter *iter = tuna_iter(db, &error);
+    CHECK(iter != NULL);
+    /* The iterator is a snapshot, later writes don't show up */
+    CHECK(tuna_set(db, "eel", "moray", &error) == TUNA_STATUS_OK);
+
+    CHECK(tuna_iter_next(iter, &key, &value, &error) == TUNA_STATUS_OK);
+    CHECK(strcmp(key, "bird") == 0 && strcmp(value, "heron") == 0);
+    tuna_free(key);
+    tuna_free(value);
+    CHECK(tuna_iter_next(iter, &key, &value, &error) == TUNA_STATUS_OK);
+    CHECK(strcmp(key, "fish") == 0 && strcmp(value, "tuna") == 0);
+    tuna_free(key);
+    tuna_free(value);
+    CHECK(tuna_iter_next(iter, &key, &value, &error) == TUNA_STATUS_NOT_FOUND);
+    tuna_iter_free(iter);
+
+    CHECK(tuna_close(db, &error) == TUNA_STATUS_OK);
+    CHECK(tuna_close(NULL, &error) == TUNA_STATUS_OK);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <database file>\n", argv[0]);
+        return 2;
+    }
+    if (test_missing_file(argv[1]) || test_unsupported_file(argv[1]) ||
+        test_read_write(argv[1]) || test_iterate(argv[1])) {
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,56 @@
+//! Compiles `tests/c/api.c` against the library and runs it.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The header generated by the build script.
+fn generated_header_dir() -> PathBuf {
+    PathBuf::from(env!("OUT_DIR"))
+}
+
+#[test]
+fn committed_header_is_up_to_date() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let generated = generated_header_dir().join("tuna.h");
+    let committed = manifest_dir.join("include/tuna.h");
+    assert!(
+        fs::read_to_string(&generated).unwrap() == fs::read_to_string(&committed).unwrap(),
+        "{} is out of date, copy {} over it",
+        committed.display(),
+        generated.display()
+    );
+}
+
+#[test]
+fn c_api() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Test binaries live in `target/<profile>/deps`, next to the library
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let lib_dir = deps_dir.parent().unwrap();
+    let work_dir = env::temp_dir().join(format!("tuna-ffi-{}", std::process::id()));
+    fs::create_dir_all(&work_dir).unwrap();
+    let binary = work_dir.join("api");
+
+    let compiled = Command::new(env::var("CC").unwrap_or("cc".to_string()))
+        .arg(manifest_dir.join("tests/c/api.c"))
+        .arg("-I")
+        .arg(generated_header_dir())
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("-ltuna")
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(compiled.success(), "failed to compile the C tests");
+
+    let status = Command::new(&binary)
+        .arg(work_dir.join("tuna.db"))
+        .env("LD_LIBRARY_PATH", lib_dir)
+        .env("DYLD_LIBRARY_PATH", lib_dir)
+        .status()
+        .unwrap();
+    fs::remove_dir_all(&work_dir).unwrap();
+    assert!(status.success(), "the C tests failed");
+}
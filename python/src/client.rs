@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyIterator, PyList};
+
+use client::{blocking, Options, TlsOptions};
+
+use crate::{ConnectionError, ServerError, TimeoutError};
+
+/**
+* Connection pool to a server, that retries the requests whose connection
+* broke:
+*
+*   client = tunadb.Client("127.0.0.1", 5880, user="reader", password="...")
+*   client["jobs:1"] = "done"
+*
+* The address is a Unix domain socket when `unix_socket` is given. TCP
+* connections use TLS with `tls=True`, or when a CA (`ca_cert`) or a client
+* certificate (`cert` and `key`) is given.
+*/
+#[pyclass(module = "tunadb")]
+pub struct Client {
+    client: blocking::Client,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[pyo3(signature = (
+        host = "127.0.0.1",
+        port = 5880,
+        *,
+        unix_socket = None,
+        tls = false,
+        ca_cert = None,
+        cert = None,
+        key = None,
+        user = None,
+        password = None,
+        pool_size = 8,
+        timeout = 30.0,
+        retries = 3,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        py: Python<'_>,
+        host: &str,
+        port: u16,
+        unix_socket: Option<String>,
+        tls: bool,
+        ca_cert: Option<String>,
+        cert: Option<String>,
+        key: Option<String>,
+        user: Option<String>,
+        password: Option<String>,
+        pool_size: usize,
+        timeout: f64,
+        retries: usize,
+    ) -> PyResult<Self> {
+        let address = match unix_socket {
+            Some(path) => Options::unix(path),
+            None => Options::tcp(format!("{}:{}", host, port)),
+        };
+        let tls = tls || ca_cert.is_some() || cert.is_some();
+        let options = Options {
+            tls: tls.then(|| TlsOptions {
+                ca_cert: ca_cert.map(Into::into),
+                cert: cert.map(Into::into),
+                key: key.map(Into::into),
+            }),
+            user,
+            password,
+            pool_size,
+            request_timeout: Duration::try_from_secs_f64(timeout)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            retries,
+            ..address
+        };
+        let client = py
+            .allow_threads(|| blocking::Client::connect(options))
+            .map_err(to_py_err)?;
+        Ok(Client { client })
+    }
+
+    /// Returns the value of the key, `default` if it doesn't exist.
+    #[pyo3(signature = (key, default = None))]
+    fn get(&self, py: Python<'_>, key: &str, default: Option<String>) -> PyResult<Option<String>> {
+        let value = py
+            .allow_threads(|| self.client.get(key))
+            .map_err(to_py_err)?;
+        Ok(value.or(default))
+    }
+
+    fn set(&self, py: Python<'_>, key: &str, value: &str) -> PyResult<()> {
+        py.allow_threads(|| self.client.set(key, value))
+            .map_err(to_py_err)
+    }
+
+    /// Deletes the key. Deleting a key that doesn't exist isn't an error.
+    fn delete(&self, py: Python<'_>, key: &str) -> PyResult<()> {
+        py.allow_threads(|| self.client.del(key)).map_err(to_py_err)
+    }
+
+    /// Returns every key, sorted.
+    fn keys(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        let mut keys = py.allow_threads(|| self.client.list()).map_err(to_py_err)?;
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn ping(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.client.ping()).map_err(to_py_err)
+    }
+
+    /// Returns the server's reply to the handshake as a dict, `None` if the
+    /// server predates it.
+    fn server_info<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(hello) = py
+            .allow_threads(|| self.client.server_info())
+            .map_err(to_py_err)?
+        else {
+            return Ok(None);
+        };
+        let info = PyDict::new(py);
+        info.set_item("protocol_version", hello.protocol_version)?;
+        info.set_item("server_version", hello.server_version)?;
+        info.set_item("features", hello.features)?;
+        Ok(Some(info))
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<String> {
+        self.get(py, key, None)?
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
+
+    fn __setitem__(&self, py: Python<'_>, key: &str, value: &str) -> PyResult<()> {
+        self.set(py, key, value)
+    }
+
+    fn __delitem__(&self, py: Python<'_>, key: &str) -> PyResult<()> {
+        if !self.__contains__(py, key)? {
+            return Err(PyKeyError::new_err(key.to_string()));
+        }
+        self.delete(py, key)
+    }
+
+    fn __contains__(&self, py: Python<'_>, key: &str) -> PyResult<bool> {
+        Ok(self.get(py, key, None)?.is_some())
+    }
+
+    fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(py
+            .allow_threads(|| self.client.list())
+            .map_err(to_py_err)?
+            .len())
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self.keys(py)?)?.try_iter()
+    }
+}
+
+fn to_py_err(error: client::Error) -> PyErr {
+    let message = error.to_string();
+    match error {
+        client::Error::Server(e) => {
+            let py_err = ServerError::new_err(e.message);
+            Python::with_gil(|py| {
+                py_err
+                    .value(py)
+                    .setattr("code", e.code.to_string())
+                    .map(|_| py_err)
+                    .unwrap_or_else(|e| e)
+            })
+        }
+        client::Error::Timeout => TimeoutError::new_err(message),
+        client::Error::Io(_) | client::Error::Protocol(_) => ConnectionError::new_err(message),
+    }
+}
@@ -0,0 +1,162 @@
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+
+use tuna_core::db::{self, DbOptions};
+
+use crate::Error;
+
+/**
+* Database file opened in-process:
+*
+*   with tunadb.Db("tuna.db") as db:
+*       db["jobs:1"] = "done"
+*       for key, value in db.items():
+*           print(key, value)
+*
+* Handles can be shared between threads, writes are serialized.
+*/
+#[pyclass(module = "tunadb")]
+pub struct Db {
+    db: db::Db,
+}
+
+/// Iterator over a snapshot of the keys or of the key-value pairs, sorted
+/// by key.
+#[pyclass(module = "tunadb")]
+pub struct DbIterator {
+    iter: db::Iter,
+    items: bool,
+}
+
+#[pymethods]
+impl Db {
+    #[new]
+    #[pyo3(signature = (path, create_if_missing = true, sync_writes = false))]
+    fn open(
+        py: Python<'_>,
+        path: String,
+        create_if_missing: bool,
+        sync_writes: bool,
+    ) -> PyResult<Self> {
+        let options = DbOptions {
+            create_if_missing,
+            sync_writes,
+        };
+        let db = py
+            .allow_threads(|| db::Db::open(path, options))
+            .map_err(to_py_err)?;
+        Ok(Db { db })
+    }
+
+    /// Returns the value of the key, `default` if it doesn't exist.
+    #[pyo3(signature = (key, default = None))]
+    fn get(&self, py: Python<'_>, key: &str, default: Option<String>) -> PyResult<Option<String>> {
+        let value = py.allow_threads(|| self.db.get(key)).map_err(to_py_err)?;
+        Ok(value.or(default))
+    }
+
+    fn set(&self, py: Python<'_>, key: &str, value: &str) -> PyResult<()> {
+        py.allow_threads(|| self.db.set(key, value))
+            .map_err(to_py_err)
+    }
+
+    /// Deletes the key. Deleting a key that doesn't exist isn't an error.
+    fn delete(&self, py: Python<'_>, key: &str) -> PyResult<()> {
+        py.allow_threads(|| self.db.delete(key)).map_err(to_py_err)
+    }
+
+    /// Returns every key, sorted.
+    fn keys(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        let mut keys = py.allow_threads(|| self.db.keys()).map_err(to_py_err)?;
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Iterates over the key-value pairs as they are when it's called.
+    fn items(&self, py: Python<'_>) -> PyResult<DbIterator> {
+        self.iterator(py, true)
+    }
+
+    /// Waits until every write is persisted to disk.
+    fn sync(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.db.sync()).map_err(to_py_err)
+    }
+
+    /// Persists the pending writes and closes the database.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.db.close()).map_err(to_py_err)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<String> {
+        self.get(py, key, None)?
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
+
+    fn __setitem__(&self, py: Python<'_>, key: &str, value: &str) -> PyResult<()> {
+        self.set(py, key, value)
+    }
+
+    fn __delitem__(&self, py: Python<'_>, key: &str) -> PyResult<()> {
+        if !self.__contains__(py, key)? {
+            return Err(PyKeyError::new_err(key.to_string()));
+        }
+        self.delete(py, key)
+    }
+
+    fn __contains__(&self, py: Python<'_>, key: &str) -> PyResult<bool> {
+        Ok(self.get(py, key, None)?.is_some())
+    }
+
+    fn __len__(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(py
+            .allow_threads(|| self.db.keys())
+            .map_err(to_py_err)?
+            .len())
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<DbIterator> {
+        self.iterator(py, false)
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: PyObject,
+        _exc_value: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<()> {
+        self.close(py)
+    }
+}
+
+impl Db {
+    fn iterator(&self, py: Python<'_>, items: bool) -> PyResult<DbIterator> {
+        let iter = py.allow_threads(|| self.db.iter()).map_err(to_py_err)?;
+        Ok(DbIterator { iter, items })
+    }
+}
+
+#[pymethods]
+impl DbIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+        Ok(Some(match self.items {
+            true => (key, value).into_pyobject(py)?.into_any(),
+            false => key.into_pyobject(py)?.into_any(),
+        }))
+    }
+}
+
+fn to_py_err(error: anyhow::Error) -> PyErr {
+    Error::new_err(error.to_string())
+}
@@ -0,0 +1,51 @@
+/*!
+* Python bindings, built as the `tunadb` extension module with maturin.
+*
+* `tunadb.Db` opens a database file in-process and `tunadb.Client` talks to
+* a server. Both behave like a dict of strings, and raise `tunadb.Error` or
+* one of its subclasses when something goes wrong.
+*/
+mod client;
+mod db;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    tunadb,
+    Error,
+    PyException,
+    "Base class of the TunaDB errors."
+);
+create_exception!(
+    tunadb,
+    ServerError,
+    Error,
+    "The server answered with an error, whose name is in `code`."
+);
+create_exception!(
+    tunadb,
+    ConnectionError,
+    Error,
+    "Couldn't connect to the server, or the connection broke."
+);
+create_exception!(
+    tunadb,
+    TimeoutError,
+    Error,
+    "The server took longer than the timeout to answer."
+);
+
+#[pymodule]
+fn tunadb(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("Error", py.get_type::<Error>())?;
+    m.add("ServerError", py.get_type::<ServerError>())?;
+    m.add("ConnectionError", py.get_type::<ConnectionError>())?;
+    m.add("TimeoutError", py.get_type::<TimeoutError>())?;
+    m.add_class::<db::Db>()?;
+    m.add_class::<client::Client>()?;
+    m.add_class::<db::DbIterator>()?;
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use anyhow::anyhow;
+use core::command::Command;
+use core::pattern;
+use core::proto::command::Operation;
+use core::response::{ErrorCode, ResponseError};
+use core::slowlog::SlowLogEntry;
+use serde::Deserialize;
+
+/**
+* Access rules of a user, declared in the users file. A command is allowed
+* when any rule lists it (or `*`) and every key it touches matches one of
+* the rule's key patterns. Users without rules have full access. FLUSHDB
+* deletes every key, so it's only allowed by rules covering all of them
+* (`keys = ["*"]`). WATCH is checked like any other command: an aborted
+* EXEC tells whether a watched key was written, so users can only watch the
+* keys their rules allow.
+*
+* Example:
+*   [[users.rules]]
+*   commands = ["get", "list"]
+*   keys = ["cache:*"]
+*
+*   [[users.rules]]
+*   commands = ["get", "set", "del"]
+*   keys = ["jobs:*"]
+*/
+#[derive(Deserialize, Clone, Default)]
+pub struct Acl {
+    #[serde(default)]
+    rules: Option<Vec<Rule>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct Rule {
+    commands: Vec<String>,
+    /// Glob patterns of the keys the commands can be run on
+    #[serde(default = "all_keys")]
+    keys: Vec<String>,
+}
+
+fn all_keys() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+/// Commands every authenticated user can run, whatever their rules say.
+/// Queued commands are checked when they're queued.
+const ALWAYS_ALLOWED: [Operation; 6] = [
+    Operation::Auth,
+    Operation::Ping,
+    Operation::Multi,
+    Operation::Exec,
+    Operation::Discard,
+    Operation::Unwatch,
+];
+
+impl Rule {
+    fn allows_command(&self, name: &str) -> bool {
+        self.commands.iter().any(|c| c == "*" || c == name)
+    }
+
+    fn allows_key(&self, key: &str) -> bool {
+        self.keys.iter().any(|p| pattern::matches(p, key))
+    }
+
+    fn allows_all_keys(&self) -> bool {
+        self.keys.iter().any(|p| p == "*")
+    }
+}
+
+impl Acl {
+    /// Checks the rules only reference existing commands.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for rule in self.rules.iter().flatten() {
+            for command in &rule.commands {
+                if command != "*" && Operation::from_str_name(&command.to_uppercase()).is_none() {
+                    return Err(anyhow!("Unknown command '{}' in ACL rule", command));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check(&self, command: &Command) -> Result<(), ResponseError> {
+        let Some(rules) = &self.rules else {
+            return Ok(());
+        };
+        if ALWAYS_ALLOWED.contains(&command.operation()) {
+            return Ok(());
+        }
+
+        let name = command.name();
+        let keys = command.keys();
+        let allowed = rules.iter().any(|rule| {
+            rule.allows_command(&name)
+                && match command {
+                    Command::Flushdb => rule.allows_all_keys(),
+                    _ => keys.iter().all(|key| rule.allows_key(key)),
+                }
+        });
+
+        if allowed {
+            return Ok(());
+        }
+        // Named after the first key no rule allows, if any, for commands with several
+        let denied = keys
+            .iter()
+            .find(|key| !self.allows(&name, key))
+            .or(keys.first());
+        Err(ResponseError::new(
+            ErrorCode::Unauthorized,
+            match denied {
+                Some(key) => format!("not allowed to run {} on '{}'", name, key),
+                None => format!("not allowed to run {}", name),
+            },
+        ))
+    }
+
+    /// Whether the user can run the command on the key.
+    pub fn allows(&self, command_name: &str, key: &str) -> bool {
+        let Some(rules) = &self.rules else {
+            return true;
+        };
+        rules
+            .iter()
+            .any(|rule| rule.allows_command(command_name) && rule.allows_key(key))
+    }
+
+    /// Keeps the keys returned by a command that the user is allowed to
+    /// run it on, so LIST doesn't leak keys outside of the user's patterns.
+    pub fn filter_keys(&self, command_name: &str, keys: Vec<String>) -> Vec<String> {
+        let Some(rules) = &self.rules else {
+            return keys;
+        };
+
+        let rules: Vec<&Rule> = rules
+            .iter()
+            .filter(|r| r.allows_command(command_name))
+            .collect();
+        keys.into_iter()
+            .filter(|key| rules.iter().any(|rule| rule.allows_key(key)))
+            .collect()
+    }
+
+    /// Drops the slow log entries of keys the user isn't allowed to run
+    /// their command on, as the log is shared by every client.
+    pub fn filter_slowlog(&self, entries: Vec<SlowLogEntry>) -> Vec<SlowLogEntry> {
+        entries
+            .into_iter()
+            .filter(|entry| match &entry.key {
+                Some(key) => !self
+                    .filter_keys(&entry.command, vec![key.clone()])
+                    .is_empty(),
+                None => true,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read-only access to the cache and read-write access to the jobs.
+    fn worker() -> Acl {
+        toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["get", "list"]
+            keys = ["cache:*"]
+
+            [[rules]]
+            commands = ["get", "set", "del"]
+            keys = ["jobs:*"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn get(key: &str) -> Command {
+        Command::Get {
+            key: key.to_string(),
+        }
+    }
+
+    fn set(key: &str) -> Command {
+        Command::Set {
+            key: key.to_string(),
+            value: "1".to_string(),
+        }
+    }
+
+    #[test]
+    fn check_allows_commands_on_matching_keys() {
+        let acl = worker();
+        assert!(acl.check(&get("cache:users")).is_ok());
+        assert!(acl.check(&get("jobs:1")).is_ok());
+        assert!(acl.check(&set("jobs:1")).is_ok());
+        assert!(acl.check(&Command::List).is_ok());
+    }
+
+    #[test]
+    fn check_rejects_commands_outside_of_the_rules() {
+        let acl = worker();
+        let error = acl.check(&set("cache:users")).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Unauthorized);
+        assert!(acl.check(&get("secrets:1")).is_err());
+        assert!(acl
+            .check(&Command::Del {
+                key: "cache:users".to_string()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn watch_is_checked_against_the_keys() {
+        let watch = |keys: &[&str]| Command::Watch {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        };
+        let acl: Acl = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["get", "watch"]
+            keys = ["jobs:*"]
+            "#,
+        )
+        .unwrap();
+        assert!(acl.check(&watch(&["jobs:1", "jobs:2"])).is_ok());
+        let error = acl.check(&watch(&["jobs:1", "secrets:1"])).unwrap_err();
+        assert_eq!(error.message, "not allowed to run watch on 'secrets:1'");
+        // Not listed in the worker's rules
+        assert!(worker().check(&watch(&["jobs:1"])).is_err());
+        assert!(worker().check(&Command::Unwatch).is_ok());
+    }
+
+    #[test]
+    fn filter_slowlog_drops_the_entries_of_hidden_keys() {
+        let entry = |command: &str, key: Option<&str>| SlowLogEntry {
+            id: 0,
+            timestamp: 0,
+            duration: std::time::Duration::from_millis(20),
+            command: command.to_string(),
+            key: key.map(|key| key.to_string()),
+            client: "127.0.0.1:40000".to_string(),
+        };
+        let entries = vec![
+            entry("get", Some("cache:users")),
+            entry("set", Some("cache:users")),
+            entry("get", Some("secrets:1")),
+            entry("list", None),
+        ];
+        let visible: Vec<(String, Option<String>)> = worker()
+            .filter_slowlog(entries)
+            .into_iter()
+            .map(|entry| (entry.command, entry.key))
+            .collect();
+        assert_eq!(
+            visible,
+            [
+                ("get".to_string(), Some("cache:users".to_string())),
+                ("list".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn flushdb_needs_a_rule_covering_every_key() {
+        assert!(worker().check(&Command::Flushdb).is_err());
+        let admin: Acl = toml::from_str(
+            r#"
+            [[rules]]
+            commands = ["*"]
+            "#,
+        )
+        .unwrap();
+        assert!(admin.check(&Command::Flushdb).is_ok());
+    }
+
+    #[test]
+    fn users_without_rules_have_full_access() {
+        let acl = Acl::default();
+        assert!(acl.check(&set("secrets:1")).is_ok());
+        assert!(acl.check(&Command::Flushdb).is_ok());
+        assert!(acl.allows("cdc", "secrets:1"));
+    }
+
+    #[test]
+    fn filter_keys_keeps_the_keys_the_command_can_see() {
+        let keys = vec![
+            "cache:users".to_string(),
+            "jobs:1".to_string(),
+            "secrets:1".to_string(),
+        ];
+        assert_eq!(
+            worker().filter_keys("list", keys.clone()),
+            vec!["cache:users".to_string()]
+        );
+        assert_eq!(
+            worker().filter_keys("get", keys),
+            vec!["cache:users".to_string(), "jobs:1".to_string()]
+        );
+    }
+}
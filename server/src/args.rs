@@ -1,4 +1,4 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug, Clone, ValueEnum)]
 pub enum LogLevel {
@@ -9,13 +9,58 @@ pub enum LogLevel {
     Trace,
 }
 
+#[derive(Parser, Debug, Clone, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long, default_value_t = LogLevel::Info)]
-    pub log_level: LogLevel,
+    /// Overrides the `log_level` config setting [default: info]
+    #[arg(short, long)]
+    pub log_level: Option<LogLevel>,
+    /// Overrides the `log_format` config setting [default: text]
+    #[arg(long)]
+    pub log_format: Option<LogFormat>,
     #[arg(short, long, default_value_t = 5880)]
     pub port: u16,
+    /// Address to listen on, can be repeated. Overrides the `bind` config setting
+    #[arg(short, long, value_name = "ADDR")]
+    pub bind: Vec<String>,
+    /// PEM certificate chain used to serve TLS. Requires --tls-key
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<String>,
+    /// PEM private key used to serve TLS. Requires --tls-cert
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<String>,
+    /// PEM file with the CAs that sign client certificates. Enables mTLS
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    pub tls_client_ca: Option<String>,
+    /// File with the user accounts. Clients must authenticate when set
+    #[arg(long, value_name = "FILE")]
+    pub users_file: Option<String>,
+    /// Unix domain socket to listen on, besides the TCP addresses
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<String>,
+    /// Octal permissions of the Unix domain socket file (e.g. 660)
+    #[arg(long, value_name = "MODE", requires = "socket")]
+    pub socket_permissions: Option<String>,
+    /// Maximum number of connected clients. Overrides the `limits.max_clients` config setting
+    #[arg(long, value_name = "N")]
+    pub max_clients: Option<usize>,
+    /// Serves Prometheus metrics over HTTP on this address (ip:port). Overrides the `metrics_address` config setting
+    #[arg(long, value_name = "ADDR")]
+    pub metrics_address: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<ServerCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ServerCommand {
+    /// Reads a password from stdin and prints its hash for the users file
+    HashPassword,
 }
 
 impl std::fmt::Display for LogLevel {
@@ -26,3 +71,12 @@ impl std::fmt::Display for LogLevel {
             .fmt(f)
     }
 }
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
@@ -0,0 +1,281 @@
+use core::command::Command;
+use core::config::AuditConfig;
+use core::response::Response;
+use core::transaction::Transaction;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+* Append-only record of the commands that change the database or the
+* server, kept apart from the database file. Each entry is a JSON object on
+* its own line.
+*
+* Once the file would grow over `max_size` it's renamed with a `.1` suffix
+* (shifting the older ones to `.2`, `.3`, ...) and a new one is started.
+*/
+pub struct AuditLog {
+    config: AuditConfig,
+    file: Mutex<AuditFile>,
+}
+
+struct AuditFile {
+    file: File,
+    size: u64,
+}
+
+/// Change a command makes, known before it runs.
+pub struct Change {
+    operation: String,
+    key: Option<String>,
+    value: Option<String>,
+}
+
+/// Changes of a request, recorded once it ran.
+pub enum Pending {
+    Command(Change),
+    /// One per command queued in the transaction, `None` for those that
+    /// don't change anything
+    Transaction(Vec<Option<Change>>),
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+    client: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<&'a str>,
+    operation: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a str>,
+    status: &'a str,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditConfig) -> anyhow::Result<AuditLog> {
+        let file = open_file(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(AuditLog {
+            config: config.clone(),
+            file: Mutex::new(AuditFile { file, size }),
+        })
+    }
+
+    /// Returns the changes the command will make, if any. Commands queued in
+    /// a transaction don't change anything until EXEC runs them.
+    pub fn pending(&self, cmd: &Command, transaction: &Transaction) -> Option<Pending> {
+        match cmd {
+            Command::Exec => Some(Pending::Transaction(
+                transaction
+                    .queued()
+                    .iter()
+                    .map(|cmd| self.change(cmd))
+                    .collect(),
+            )),
+            _ if transaction.is_active() => None,
+            cmd => self.change(cmd).map(Pending::Command),
+        }
+    }
+
+    fn change(&self, cmd: &Command) -> Option<Change> {
+        let (key, value) = match cmd {
+            Command::Set { key, value } => (
+                Some(key.clone()),
+                self.config.log_values.then(|| value.clone()),
+            ),
+            Command::Del { key } => (Some(key.clone()), None),
+            // Settings aren't user data, so their values are always recorded
+            Command::ConfigSet { parameter, value } => {
+                (Some(parameter.clone()), Some(value.clone()))
+            }
+            Command::Flushdb | Command::ConfigRewrite | Command::SlowlogReset => (None, None),
+            _ => return None,
+        };
+        Some(Change {
+            operation: cmd.name(),
+            key,
+            value,
+        })
+    }
+
+    /// Records the changes of a request that ran. For a transaction, only
+    /// the commands EXEC ran are recorded, with their own status.
+    pub fn record(&self, pending: Pending, client: &str, user: Option<&str>, response: &Response) {
+        let changes: Vec<(Change, &Response)> = match pending {
+            Pending::Command(change) => vec![(change, response)],
+            Pending::Transaction(changes) => changes
+                .into_iter()
+                .zip(&response.results)
+                .filter_map(|(change, response)| Some((change?, response)))
+                .collect(),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+
+        for (change, response) in changes {
+            let entry = Entry {
+                timestamp,
+                client,
+                user,
+                operation: &change.operation,
+                key: change.key.as_deref(),
+                value: change.value.as_deref(),
+                status: if response.error.is_none() {
+                    "ok"
+                } else {
+                    "error"
+                },
+            };
+            // The command already ran, so a failure can only be reported
+            if let Err(e) = self.write(&entry) {
+                tracing::error!(error = %e, "Failed to write to the audit log");
+            }
+        }
+    }
+
+    fn write(&self, entry: &Entry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut audit_file = self.file.lock().unwrap();
+        let max_size = self.config.max_size;
+        if max_size > 0 && audit_file.size > 0 && audit_file.size + line.len() as u64 > max_size {
+            self.rotate()?;
+            *audit_file = AuditFile {
+                file: open_file(&self.config.path)?,
+                size: 0,
+            };
+        }
+        audit_file.file.write_all(&line)?;
+        audit_file.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts the rotated files by one, dropping the oldest, and moves the
+    /// current one to `.1`.
+    fn rotate(&self) -> std::io::Result<()> {
+        let path = &self.config.path;
+        let max_files = self.config.max_files;
+        if max_files == 0 {
+            return fs::remove_file(path);
+        }
+        let _ = fs::remove_file(format!("{}.{}", path, max_files));
+        for n in (1..max_files).rev() {
+            let from = format!("{}.{}", path, n);
+            if fs::metadata(&from).is_ok() {
+                fs::rename(&from, format!("{}.{}", path, n + 1))?;
+            }
+        }
+        fs::rename(path, format!("{}.1", path))
+    }
+}
+
+fn open_file(path: &str) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Directory of a test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("tuna-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn set(audit: &AuditLog, key: &str) {
+        let cmd = Command::Set {
+            key: key.to_string(),
+            value: "value".to_string(),
+        };
+        let pending = audit.pending(&cmd, &Transaction::default()).unwrap();
+        audit.record(pending, "127.0.0.1:5000", None, &Response::ok(None));
+    }
+
+    /// Keys of the entries of the file, `None` if it doesn't exist.
+    fn keys(path: &str) -> Option<Vec<String>> {
+        let contents = fs::read_to_string(path).ok()?;
+        Some(
+            contents
+                .lines()
+                .map(|line| {
+                    let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                    entry["key"].as_str().unwrap().to_string()
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn files_are_rotated_once_they_reach_the_max_size() {
+        let dir = TempDir::new("audit-rotation");
+        let path = dir.0.join("audit.log").display().to_string();
+        // Room for 2 entries per file
+        let max_size = 200;
+        let audit = AuditLog::open(&AuditConfig {
+            path: path.clone(),
+            log_values: false,
+            max_size,
+            max_files: 2,
+        })
+        .unwrap();
+
+        for n in 0..10 {
+            set(&audit, &format!("key-{}", n));
+        }
+
+        let current = keys(&path).unwrap();
+        let rotated_1 = keys(&format!("{}.1", path)).unwrap();
+        let rotated_2 = keys(&format!("{}.2", path)).unwrap();
+        assert_eq!(keys(&format!("{}.3", path)), None);
+        for file in [&path, &format!("{}.1", path), &format!("{}.2", path)] {
+            assert!(fs::metadata(file).unwrap().len() <= max_size);
+        }
+
+        // The newest entries are in the current file, the oldest ones were dropped
+        assert_eq!(current, ["key-8", "key-9"]);
+        assert_eq!(rotated_1, ["key-6", "key-7"]);
+        assert_eq!(rotated_2, ["key-4", "key-5"]);
+    }
+
+    #[test]
+    fn files_are_dropped_when_no_rotated_files_are_kept() {
+        let dir = TempDir::new("audit-no-rotated-files");
+        let path = dir.0.join("audit.log").display().to_string();
+        let audit = AuditLog::open(&AuditConfig {
+            path: path.clone(),
+            log_values: false,
+            max_size: 200,
+            max_files: 0,
+        })
+        .unwrap();
+
+        for n in 0..5 {
+            set(&audit, &format!("key-{}", n));
+        }
+
+        assert_eq!(keys(&path).unwrap(), ["key-4"]);
+        assert_eq!(keys(&format!("{}.1", path)), None);
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::anyhow;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString};
+use argon2::{Argon2, PasswordVerifier};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, sync::Arc};
+
+use crate::acl::Acl;
+
+/**
+* User accounts allowed to connect to the server, loaded from a TOML file
+* where passwords are stored as argon2 hashes (PHC strings).
+*
+* Example:
+*   [[users]]
+*   name = "admin"
+*   password_hash = "$argon2id$v=19$m=19456,t=2,p=1$..."
+*
+* Hashes can be generated with `tuna-server hash-password`. Each user can
+* also have access rules (see `Acl`).
+*/
+pub struct Users {
+    users: HashMap<String, Arc<User>>,
+    /// Verified against when the user doesn't exist, so unknown users take
+    /// as long to reject as wrong passwords
+    dummy_hash: String,
+}
+
+#[derive(Deserialize)]
+struct UsersFile {
+    #[serde(default)]
+    users: Vec<User>,
+}
+
+#[derive(Deserialize)]
+pub struct User {
+    pub name: String,
+    password_hash: String,
+    #[serde(flatten)]
+    pub acl: Acl,
+}
+
+impl Users {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Couldn't read users file {}: {}", path, e))?;
+        let file: UsersFile = toml::from_str(&contents)
+            .map_err(|e| anyhow!("Couldn't parse users file {}: {}", path, e))?;
+
+        let mut users = HashMap::new();
+        for user in file.users {
+            PasswordHash::new(&user.password_hash)
+                .map_err(|e| anyhow!("Invalid password hash for user {}: {}", user.name, e))?;
+            user.acl
+                .validate()
+                .map_err(|e| anyhow!("Invalid rules for user {}: {}", user.name, e))?;
+            users.insert(user.name.clone(), Arc::new(user));
+        }
+        Ok(Users {
+            users,
+            dummy_hash: hash_password("")?,
+        })
+    }
+
+    /// Returns the user if the password matches. Verifying is CPU bound, so
+    /// it should be called outside of the async executor.
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<Arc<User>> {
+        let user = self.users.get(name);
+        let hash = user.map_or(&self.dummy_hash, |user| &user.password_hash);
+        // Safe to unwrap because the hashes are validated when loading the file
+        let hash = PasswordHash::new(hash).unwrap();
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        user.filter(|_| verified).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+}
+
+/// Hashes a password in the format expected by the users file.
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("Couldn't hash password: {}", e))?;
+    Ok(hash.to_string())
+}
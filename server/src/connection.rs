@@ -0,0 +1,574 @@
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use core::cdc::ChangeStream;
+use core::command::{self, Command, Context};
+use core::config::{Config, LimitsConfig};
+use core::frame;
+use core::metrics::METRICS;
+use core::proto::command::Operation;
+use core::pubsub::{Message, Subscription, KEYSPACE_CHANNEL_PREFIX};
+use core::response::{ErrorCode, Response, ResponseError};
+use core::serializer::{CommandSerializer, ResponseSerializer};
+use core::slowlog::{SlowLog, SlowLogEntry};
+use core::storage::LogRecord;
+use core::transaction::Transaction;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
+
+use crate::audit::AuditLog;
+use crate::auth::{User, Users};
+use crate::logging::LevelHandle;
+use crate::settings;
+
+/// Tags the logs of each command, so they can be told apart from the ones of
+/// other commands of the same connection.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// State shared by every connection, regardless of the listener it came from.
+pub struct Shared {
+    pub ctx: Context,
+    pub command_serializer: Box<dyn CommandSerializer>,
+    pub response_serializer: Box<dyn ResponseSerializer>,
+    /// Accounts clients must authenticate with. Authentication is disabled when `None`
+    pub users: Option<Arc<Users>>,
+    /// Settings of the server, changed at runtime with CONFIG SET
+    pub config: RwLock<Config>,
+    /// Settings of the config file, with the changes made by CONFIG SET but
+    /// without the command line overrides, as CONFIG REWRITE saves them
+    pub file_config: Mutex<Config>,
+    /// One permit per client allowed to be connected at the same time
+    pub clients: Arc<Semaphore>,
+    /// Level the logs are capped to, changed with CONFIG SET
+    pub log_level: LevelHandle,
+    /// Latest commands that took longer than the configured threshold
+    pub slowlog: SlowLog,
+    /// Record of the commands that change something, if enabled
+    pub audit: Option<AuditLog>,
+    /// Set to true when the server starts shutting down
+    pub shutdown: watch::Receiver<bool>,
+}
+
+impl Shared {
+    /// Current limits, which may change between two calls.
+    pub fn limits(&self) -> LimitsConfig {
+        self.config.read().unwrap().limits.clone()
+    }
+
+    /// Number of clients currently connected.
+    pub fn connected_clients(&self) -> usize {
+        self.limits()
+            .max_clients
+            .saturating_sub(self.clients.available_permits())
+    }
+}
+
+/// State of a single client connection.
+struct Session {
+    peer: String,
+    /// Authenticated user, if any
+    user: Option<Arc<User>>,
+    /// Channels and patterns the client is subscribed to, if any
+    subscription: Option<Subscription>,
+    /// Log the client is following with CDC, if any
+    change_stream: Option<ChangeStream>,
+    transaction: Transaction,
+    /// Closes the connection once the current response is written
+    closing: bool,
+}
+
+impl Session {
+    /// Returns the session's subscription, registering it with the broker if needed.
+    fn ensure_subscription(&mut self, shared: &Shared) -> &mut Subscription {
+        self.subscription
+            .get_or_insert_with(|| shared.ctx.broker.subscription())
+    }
+
+    /// Drops the changes of keys the user isn't allowed to follow.
+    fn visible_changes(&self, mut changes: Vec<LogRecord>) -> Vec<LogRecord> {
+        if let Some(user) = &self.user {
+            let name = Operation::Cdc.as_str_name().to_lowercase();
+            changes.retain(|change| user.acl.allows(&name, &change.key));
+        }
+        changes
+    }
+
+    /// Whether the message can be delivered. Keyspace notifications of keys
+    /// the user isn't allowed to subscribe to are dropped, as their channel
+    /// names the key.
+    fn is_visible(&self, message: &Message) -> bool {
+        let (Some(user), Some(key)) = (
+            &self.user,
+            message.channel.strip_prefix(KEYSPACE_CHANNEL_PREFIX),
+        ) else {
+            return true;
+        };
+        let operation = match message.pattern {
+            Some(_) => Operation::Psubscribe,
+            None => Operation::Subscribe,
+        };
+        user.acl
+            .allows(&operation.as_str_name().to_lowercase(), key)
+    }
+}
+
+/// Takes a slot for a new client, `None` if there are already too many.
+/// It's released when dropped.
+pub fn admit(shared: &Shared) -> Option<OwnedSemaphorePermit> {
+    shared.clients.clone().try_acquire_owned().ok()
+}
+
+/// Serves a client connection until it's closed, holding the client's slot
+/// taken with `admit`. Without one the connection is rejected. Everything
+/// logged meanwhile is tagged with the client's address.
+#[tracing::instrument(name = "connection", skip_all, fields(client = %peer))]
+pub async fn handle<S>(
+    mut socket: S,
+    peer: String,
+    shared: Arc<Shared>,
+    permit: Option<OwnedSemaphorePermit>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Held until the connection is closed
+    let Some(_permit) = permit else {
+        tracing::warn!("Rejected connection: too many clients");
+        let response = Response::error(ResponseError::new(
+            ErrorCode::TooManyClients,
+            format!(
+                "max number of clients reached ({})",
+                shared.limits().max_clients
+            ),
+        ));
+        let _ = write_response(&mut socket, &shared, &response).await;
+        let _ = socket.shutdown().await;
+        return;
+    };
+
+    tracing::debug!("Accepted connection");
+    let mut session = Session {
+        peer,
+        user: None,
+        subscription: None,
+        change_stream: None,
+        transaction: Transaction::default(),
+        closing: false,
+    };
+    let mut shutdown = shared.shutdown.clone();
+    // Split so messages can be pushed to subscribers while waiting for a command
+    let (mut reader, mut writer) = tokio::io::split(socket);
+
+    // In a loop, read commands from the socket and write the responses back.
+    'connection: loop {
+        let limits = shared.limits();
+        // Subscribers and CDC followers are expected to sit waiting for data
+        let idle_timeout = match (&session.subscription, &session.change_stream) {
+            (None, None) => limits.idle_timeout(),
+            _ => None,
+        };
+        // Only waiting for a new command is interrupted by a shutdown, commands
+        // that already arrived are run to completion
+        let read = {
+            let read = with_timeout(idle_timeout, frame::read_frame_length(&mut reader));
+            tokio::pin!(read);
+            loop {
+                tokio::select! {
+                    read = &mut read => break read,
+                    message = next_message(&mut session.subscription) => {
+                        let Some(message) = message else {
+                            tracing::warn!("Closing connection: subscriber can't keep up");
+                            break 'connection;
+                        };
+                        if !session.is_visible(&message) {
+                            continue;
+                        }
+                        let response = Response::message(message);
+                        if let Err(e) = write_response(&mut writer, &shared, &response).await {
+                            write_failed(&e);
+                            break 'connection;
+                        }
+                    }
+                    changes = next_changes(&mut session.change_stream) => {
+                        let response = match changes {
+                            Ok(changes) => Response::changes(session.visible_changes(changes)),
+                            Err(e) => {
+                                tracing::warn!(error = %e, "CDC stream failed");
+                                session.change_stream = None;
+                                Response::error(e)
+                            }
+                        };
+                        if response.changes.is_empty() && response.error.is_none() {
+                            continue;
+                        }
+                        if let Err(e) = write_response(&mut writer, &shared, &response).await {
+                            write_failed(&e);
+                            break 'connection;
+                        }
+                    }
+                    // Mapped to a bool as the guard it returns can't be held across the writes above
+                    _ = async { shutdown.wait_for(|stopping| *stopping).await.is_ok() } => {
+                        tracing::debug!("Closing connection: server shutting down");
+                        break 'connection;
+                    }
+                }
+            }
+        };
+        let length = match read {
+            Ok(Some(length)) => length,
+            // socket closed
+            Ok(None) => break,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                tracing::debug!("Closing idle connection");
+                break;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to read from socket");
+                break;
+            }
+        };
+
+        // The rest of the request can't be skipped safely, so the connection is closed
+        if length > limits.max_request_size {
+            tracing::warn!(length, "Closing connection: request is too large");
+            let response = Response::error(ResponseError::new(
+                ErrorCode::RequestTooLarge,
+                format!(
+                    "request is {} bytes long, the maximum is {}",
+                    length, limits.max_request_size
+                ),
+            ));
+            let _ = write_response(&mut writer, &shared, &response).await;
+            break;
+        }
+
+        let frame = match with_timeout(
+            limits.read_timeout(),
+            frame::read_frame_payload(&mut reader, length),
+        )
+        .await
+        {
+            Ok(frame) => {
+                METRICS.network_received((frame::FRAME_LENGTH_SIZE + length) as u64);
+                frame
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to read request");
+                break;
+            }
+        };
+
+        // TODO: refactor
+        let response = match shared.command_serializer.decode(&frame) {
+            Ok(cmd) => {
+                let name = cmd.name();
+                let key = cmd.keys().first().map(|key| key.to_string());
+                let span = tracing::info_span!(
+                    "command",
+                    request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+                    command = %name,
+                    key = key.as_deref(),
+                );
+                // The whole command carries the values, so it's only logged when debugging
+                span.in_scope(|| tracing::debug!(request = ?cmd, "Received command"));
+                // WAIT blocks on purpose, it would fill the log
+                let timed = !matches!(cmd, Command::Wait { .. });
+                let audited = shared
+                    .audit
+                    .as_ref()
+                    .and_then(|audit| audit.pending(&cmd, &session.transaction));
+                let started = Instant::now();
+                let response = dispatch(&mut session, &shared, cmd)
+                    .instrument(span.clone())
+                    .await;
+                if let (Some(audit), Some(audited)) = (&shared.audit, audited) {
+                    let user = session.user.as_ref().map(|user| user.name.as_str());
+                    audit.record(audited, &session.peer, user, &response);
+                }
+                let elapsed = started.elapsed();
+                let ok = response.error.is_none();
+                span.in_scope(|| {
+                    tracing::info!(
+                        status = if ok { "ok" } else { "error" },
+                        duration_us = elapsed.as_micros() as u64,
+                        "Ran command"
+                    )
+                });
+                METRICS.command(&name, ok, elapsed);
+                if timed {
+                    record_if_slow(&shared, &session, name, key, elapsed);
+                }
+                response
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to decode command");
+                Response::error(ResponseError::from(&e))
+            }
+        };
+
+        if let Err(e) = write_response(&mut writer, &shared, &response).await {
+            write_failed(&e);
+            break;
+        }
+        if session.closing {
+            break;
+        }
+    }
+
+    tracing::debug!("Connection closed");
+}
+
+/// Adds the command to the slow log if it took at least the threshold.
+fn record_if_slow(
+    shared: &Shared,
+    session: &Session,
+    command: String,
+    key: Option<String>,
+    duration: Duration,
+) {
+    let config = shared.config.read().unwrap().slowlog.clone();
+    if duration < config.threshold() {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let entry = SlowLogEntry {
+        id: 0,
+        timestamp,
+        duration,
+        command,
+        key,
+        client: session.peer.clone(),
+    };
+    shared.slowlog.record(entry, config.max_len);
+}
+
+/// Writes the response, failing with `TimedOut` if the client doesn't read
+/// it in time, so a client that stopped reading can't hold its slot forever.
+async fn write_response<S>(
+    socket: &mut S,
+    shared: &Shared,
+    response: &Response,
+) -> anyhow::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::with_capacity(shared.response_serializer.encoded_len(response));
+    shared.response_serializer.encode(response, &mut buf)?;
+    with_timeout(
+        shared.limits().write_timeout(),
+        frame::write_frame(socket, &buf),
+    )
+    .await?;
+    METRICS.network_sent((frame::FRAME_LENGTH_SIZE + buf.len()) as u64);
+    Ok(())
+}
+
+/// Logs why a response couldn't be written. The connection is closed either way.
+fn write_failed(e: &anyhow::Error) {
+    match e.downcast_ref::<io::Error>() {
+        Some(e) if e.kind() == io::ErrorKind::TimedOut => {
+            tracing::warn!("Closing connection: client isn't reading its responses")
+        }
+        _ => tracing::error!(error = %e, "Failed to write to socket"),
+    }
+}
+
+/// Waits for a message on the subscription, or forever if there's none.
+async fn next_message(subscription: &mut Option<Subscription>) -> Option<Message> {
+    match subscription {
+        Some(subscription) => subscription.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for the next batch of changes of the log, or forever if the
+/// session isn't following it.
+async fn next_changes(
+    change_stream: &mut Option<ChangeStream>,
+) -> Result<Vec<LogRecord>, ResponseError> {
+    match change_stream {
+        Some(change_stream) => change_stream.next_batch().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Awaits the future, failing with `TimedOut` if it takes longer than the
+/// timeout (if there's one).
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?,
+        None => future.await,
+    }
+}
+
+/// Runs a command on behalf of the session, enforcing authentication when
+/// the server has users configured.
+async fn dispatch(session: &mut Session, shared: &Shared, cmd: Command) -> Response {
+    // Negotiated before authenticating, so clients can tell whether they can
+    // talk to the server at all
+    if let Command::Hello { .. } = cmd {
+        return hello(session, shared, cmd).await;
+    }
+    let Some(users) = &shared.users else {
+        return execute(session, shared, cmd).await;
+    };
+
+    match cmd {
+        Command::Auth { user, password } => {
+            let users = users.clone();
+            let authenticated =
+                tokio::task::spawn_blocking(move || users.authenticate(&user, &password.0)).await;
+
+            match authenticated {
+                Ok(Some(user)) => {
+                    tracing::info!(user = %user.name, "Authenticated");
+                    session.user = Some(user);
+                    Response::ok(None)
+                }
+                Ok(None) => {
+                    tracing::warn!("Failed to authenticate");
+                    Response::error(ResponseError::new(
+                        ErrorCode::Unauthorized,
+                        "invalid username or password",
+                    ))
+                }
+                Err(e) => Response::error(ResponseError::new(ErrorCode::Internal, e.to_string())),
+            }
+        }
+        Command::Ping { .. } => command::run_proto(&shared.ctx, cmd).await,
+        _ => {
+            let Some(user) = &session.user else {
+                return Response::error(ResponseError::new(
+                    ErrorCode::Unauthorized,
+                    "authentication required",
+                ));
+            };
+            // Checked before the command gets anywhere near the engine
+            if let Err(e) = user.acl.check(&cmd) {
+                tracing::warn!(user = %user.name, "Denied command");
+                return Response::error(e);
+            }
+
+            let name = cmd.name();
+            let user = user.clone();
+            let mut response = execute(session, shared, cmd).await;
+            response.keys = user.acl.filter_keys(&name, response.keys);
+            // Only LIST returns keys
+            let list = Operation::List.as_str_name().to_lowercase();
+            for result in &mut response.results {
+                result.keys = user
+                    .acl
+                    .filter_keys(&list, std::mem::take(&mut result.keys));
+            }
+            response.slowlog = user
+                .acl
+                .filter_slowlog(std::mem::take(&mut response.slowlog));
+            response
+        }
+    }
+}
+
+/// Negotiates the protocol version, closing the connection if the client's
+/// is incompatible.
+async fn hello(session: &mut Session, shared: &Shared, cmd: Command) -> Response {
+    let mut response = command::run_proto(&shared.ctx, cmd).await;
+    let Some(hello) = &mut response.hello else {
+        tracing::warn!("Closing connection: incompatible protocol");
+        session.closing = true;
+        return response;
+    };
+
+    hello.server_version = env!("CARGO_PKG_VERSION").to_string();
+    if shared.users.is_some() {
+        hello.features.push("auth".to_string());
+    }
+    if shared.ctx.keyspace_notifications {
+        hello.features.push("keyspace-notifications".to_string());
+    }
+    tracing::debug!(
+        protocol_version = hello.protocol_version,
+        "Negotiated protocol version"
+    );
+    response
+}
+
+/// Runs a command the session is allowed to run. Subscriptions belong to the
+/// session, so they're handled here rather than in core.
+async fn execute(session: &mut Session, shared: &Shared, cmd: Command) -> Response {
+    if let Err(e) = cmd.validate() {
+        return Response::error(e);
+    }
+    let transaction = &mut session.transaction;
+    match cmd {
+        Command::Multi => return transaction.multi(),
+        Command::Exec => return transaction.exec(&shared.ctx).await,
+        Command::Discard => return transaction.discard(),
+        Command::Watch { keys } => return transaction.watch(&shared.ctx, &keys),
+        Command::Unwatch => return transaction.unwatch(),
+        cmd if transaction.is_active() => return transaction.queue(cmd),
+        _ => {}
+    }
+    match cmd {
+        Command::Subscribe { channels } => session.ensure_subscription(shared).subscribe(&channels),
+        Command::Unsubscribe { channels } => {
+            session.ensure_subscription(shared).unsubscribe(&channels)
+        }
+        Command::Psubscribe { patterns } => {
+            session.ensure_subscription(shared).psubscribe(&patterns)
+        }
+        Command::Punsubscribe { patterns } => {
+            session.ensure_subscription(shared).punsubscribe(&patterns)
+        }
+        Command::Cdc { position } => {
+            return match ChangeStream::open(&shared.ctx, position).await {
+                Ok(change_stream) => {
+                    tracing::info!(position, "Following the log");
+                    session.change_stream = Some(change_stream);
+                    Response::ok(None)
+                }
+                Err(e) => Response::error(e),
+            };
+        }
+        Command::Info => {
+            let mut response = command::run_proto(&shared.ctx, cmd).await;
+            if let Some(info) = &mut response.info {
+                info.server_version = env!("CARGO_PKG_VERSION").to_string();
+                info.connected_clients = shared.connected_clients() as u64;
+            }
+            return response;
+        }
+        Command::ConfigGet { pattern } => return settings::get(shared, &pattern),
+        Command::ConfigSet { parameter, value } => {
+            return settings::set(shared, &parameter, &value)
+        }
+        Command::ConfigRewrite => return settings::rewrite(shared),
+        Command::SlowlogGet { count } => {
+            return Response::slowlog(shared.slowlog.get(count as usize))
+        }
+        Command::SlowlogReset => {
+            shared.slowlog.reset();
+            return Response::ok(None);
+        }
+        Command::SlowlogLen => return Response::integer(shared.slowlog.len() as i64),
+        _ => return command::run_proto(&shared.ctx, cmd).await,
+    }
+
+    let count = session.ensure_subscription(shared).count();
+    tracing::debug!(count, "Subscribed to channels and patterns");
+    // Back to a regular connection
+    if count == 0 {
+        session.subscription = None;
+    }
+    Response::integer(count as i64)
+}
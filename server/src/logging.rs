@@ -0,0 +1,48 @@
+use core::config::Config;
+use std::io::IsTerminal;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Handle to the level the logs are capped to, so it can be changed at runtime.
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+/// Level the logs are capped to by the config. Validated when the config
+/// is loaded or changed.
+pub fn level(config: &Config) -> LevelFilter {
+    config.log_level.parse().unwrap_or(LevelFilter::INFO)
+}
+
+/**
+* Installs the global subscriber, writing the events to stderr as text or
+* as one JSON object per line, depending on `log_format`.
+*
+* The config's level caps what's logged. When `RUST_LOG` is set it filters
+* the events too, so the config can only make the logs less verbose.
+*/
+pub fn init(config: &Config) -> anyhow::Result<LevelHandle> {
+    let env_filter = EnvFilter::try_from_default_env().ok();
+    let initial_level = match env_filter {
+        Some(_) => LevelFilter::TRACE,
+        None => level(config),
+    };
+    let (level, handle) = reload::Layer::new(initial_level);
+    let json = config.log_format == "json";
+    let ansi = std::io::stderr().is_terminal();
+
+    tracing_subscriber::registry()
+        .with(level)
+        .with(env_filter)
+        .with(json.then(|| fmt::layer().json().with_writer(std::io::stderr)))
+        .with((!json).then(|| fmt::layer().with_ansi(ansi).with_writer(std::io::stderr)))
+        .try_init()?;
+    Ok(handle)
+}
+
+/// Caps the logs to a new level.
+pub fn set_level(handle: &LevelHandle, level: LevelFilter) {
+    if let Err(e) = handle.reload(level) {
+        tracing::warn!(error = %e, "Failed to change the log level");
+    }
+}
@@ -1,30 +1,45 @@
 use anyhow::anyhow;
-use args::Args;
+use args::{Args, ServerCommand};
+use audit::AuditLog;
+use auth::Users;
 use clap::Parser;
+use connection::Shared;
 use core::serializer::{CommandSerializer, ProtoCommandSerializer, ResponseSerializer};
-use env_logger::Env;
-use prost::Message;
 use std::process::ExitCode;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
 
-use core::command::{self};
-use core::config;
+use core::command::Context;
+use core::config::{self, TlsConfig, UnixSocketConfig};
+use core::slowlog::SlowLog;
 use core::storage;
-use log;
 
+mod acl;
 mod args;
+mod audit;
+mod auth;
+mod connection;
+mod logging;
+mod metrics;
+mod settings;
+mod shutdown;
 mod tcp;
+mod tls;
+mod unix;
 
 #[tokio::main]
 async fn main() -> ExitCode {
     match init().await {
         Ok(_) => ExitCode::from(0),
         Err(e) => {
-            log::error!("Error: {}", e);
-            eprintln!("Error: {}", e);
+            // Errors found before the logs are set up can only go to stderr
+            if tracing::dispatcher::has_been_set() {
+                tracing::error!(error = %e, "Server failed");
+            } else {
+                eprintln!("Error: {}", e);
+            }
             ExitCode::from(1)
         }
     }
@@ -42,72 +57,174 @@ fn new_response_serializer() -> Box<dyn ResponseSerializer> {
 
 async fn init() -> anyhow::Result<()> {
     let args = Args::parse();
-    if !tcp::local_port_available(args.port) {
-        return Err(anyhow!("Port {} is already in use", args.port));
+
+    if let Some(ServerCommand::HashPassword) = args.command {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        println!(
+            "{}",
+            auth::hash_password(password.trim_end_matches(['\r', '\n']))?
+        );
+        return Ok(());
     }
-    env_logger::init_from_env(Env::default().default_filter_or(args.log_level.to_string()));
-
-    log::info!("Starting server in port {}...", args.port);
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", args.port)).await?;
-    let config = config::parse()?;
-    let engine = Arc::new(Mutex::new(storage::new_engine(&config.file_path)?));
-    let command_serializer = Arc::new(new_command_serializer());
-    let response_serializer = Arc::new(new_response_serializer());
-
-    log::info!("Server started");
-    loop {
-        let command_serializer = command_serializer.clone();
-        let response_serializer = response_serializer.clone();
-        let (mut socket, _) = listener.accept().await?;
-        let engine = engine.clone(); // Clone the engine pointer for each connection
-
-        tokio::spawn(async move {
-            let mut buf = [0; 1024];
-
-            // In a loop, read data from the socket and write the data back.
-            loop {
-                let n = match socket.read(&mut buf).await {
-                    // socket closed
-                    Ok(n) if n == 0 => return,
-                    Ok(n) => n,
-                    Err(e) => {
-                        log::error!("failed to read from socket; err = {:?}", e);
-                        eprintln!("failed to read from socket; err = {:?}", e);
-                        return;
-                    }
-                };
-
-                // TODO: refactor
-                match command_serializer.decode(&buf[0..n]) {
-                    Ok(cmd) => {
-                        log::info!("Received command: {:?}", cmd);
-                        let response = command::run_proto(engine.clone(), cmd).await; // Pass a reference to the engine
-                        let mut buf = Vec::new();
-                        buf.reserve(response_serializer.encoded_len(&response));
-
-                        match response_serializer.encode(&response, &mut buf) {
-                            Ok(_) => {
-                                let _ = socket.write_all(&buf).await;
-                            }
-                            Err(e) => {
-                                if let Err(e) = socket.write_all(e.to_string().as_bytes()).await {
-                                    log::error!("failed to encode response; err = {:?}", e);
-                                    eprintln!("failed to encode response; err = {:?}", e);
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if let Err(e) = socket.write_all(e.to_string().as_bytes()).await {
-                            log::error!("failed to write to socket; err = {:?}", e);
-                            eprintln!("failed to write to socket; err = {:?}", e);
-                            return;
-                        }
-                        continue;
-                    }
-                }
-            }
-        });
+
+    let file_config = config::parse()?;
+    let mut config = file_config.clone();
+    if let Some(log_level) = &args.log_level {
+        config.log_level = log_level.to_string();
+    }
+    if let Some(log_format) = &args.log_format {
+        config.log_format = log_format.to_string();
+    }
+    if let Some(max_clients) = args.max_clients {
+        config.limits.max_clients = max_clients;
+    }
+    config.validate()?;
+
+    let log_level = logging::init(&config)?;
+
+    let bind = if args.bind.is_empty() {
+        &config.bind
+    } else {
+        &args.bind
+    };
+    let addresses = bind
+        .iter()
+        .map(|address| tcp::parse_bind_address(address, args.port))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let unix_socket = match &args.socket {
+        Some(path) => Some(UnixSocketConfig {
+            path: path.clone(),
+            permissions: args.socket_permissions.clone(),
+        }),
+        None => config.unix_socket.clone(),
+    };
+    if addresses.is_empty() && unix_socket.is_none() {
+        return Err(anyhow!("No bind addresses configured"));
+    }
+
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert_file), Some(key_file)) => Some(TlsConfig {
+            cert_file: cert_file.clone(),
+            key_file: key_file.clone(),
+            client_ca_file: args.tls_client_ca.clone(),
+        }),
+        _ => config.tls.clone(),
+    };
+    let tls_acceptor = tls_config.as_ref().map(tls::acceptor).transpose()?;
+    let users = match args.users_file.as_ref().or(config.users_file.as_ref()) {
+        Some(users_file) => {
+            let users = Users::load(users_file)?;
+            tracing::info!("Authentication enabled ({} users)", users.len());
+            Some(Arc::new(users))
+        }
+        None => None,
+    };
+
+    tracing::info!("Starting server in port {}...", args.port);
+    let listeners = tcp::bind_all(&addresses).await?;
+    let unix_listener = unix_socket.as_ref().map(unix::bind).transpose()?;
+    let metrics_listener = match args
+        .metrics_address
+        .as_ref()
+        .or(config.metrics_address.as_ref())
+    {
+        Some(address) => Some(metrics::bind(address).await?),
+        None => None,
+    };
+    let audit = match &config.audit {
+        Some(audit) => {
+            tracing::info!("Audit log enabled ({})", audit.path);
+            Some(
+                AuditLog::open(audit)
+                    .map_err(|e| anyhow!("Couldn't open {}: {}", audit.path, e))?,
+            )
+        }
+        None => None,
+    };
+    let mut ctx = Context::new(storage::new_engine(&config.file_path)?);
+    ctx.keyspace_notifications = config.keyspace_notifications;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shared = Arc::new(Shared {
+        ctx,
+        command_serializer: new_command_serializer(),
+        response_serializer: new_response_serializer(),
+        users,
+        clients: Arc::new(Semaphore::new(config.limits.max_clients)),
+        log_level,
+        slowlog: SlowLog::default(),
+        audit,
+        config: RwLock::new(config.clone()),
+        file_config: Mutex::new(file_config),
+        shutdown: shutdown_rx,
+    });
+
+    let mut accept_loops = JoinSet::new();
+    for listener in listeners {
+        match &tls_acceptor {
+            Some(_) => tracing::info!("Listening on {} (TLS)", listener.local_addr()?),
+            None => tracing::info!("Listening on {}", listener.local_addr()?),
+        }
+        accept_loops.spawn(tcp::accept(listener, tls_acceptor.clone(), shared.clone()));
+    }
+    if let (Some(listener), Some(unix_socket)) = (unix_listener, &unix_socket) {
+        tracing::info!("Listening on {}", unix_socket.path);
+        accept_loops.spawn(unix::accept(
+            listener,
+            unix_socket.path.clone(),
+            shared.clone(),
+        ));
+    }
+    if let Some(listener) = metrics_listener {
+        tracing::info!(
+            "Serving metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+        accept_loops.spawn(metrics::accept(listener, shared.clone()));
+    }
+
+    tracing::info!("Server started");
+    tokio::select! {
+        result = serve(&mut accept_loops) => return result,
+        signal = shutdown::signal_received() => tracing::info!("Received {}, shutting down...", signal?),
+    }
+
+    // Stop accepting connections and let the open ones finish their commands
+    shutdown_tx.send_replace(true);
+    serve(&mut accept_loops).await?;
+
+    let (timeout, max_clients) = {
+        let config = shared.config.read().unwrap();
+        (
+            Duration::from_secs(config.shutdown_timeout_secs),
+            config.limits.max_clients as u32,
+        )
+    };
+    if tokio::time::timeout(timeout, shared.clients.acquire_many(max_clients))
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "{} connections still open after {}s, closing them",
+            shared.connected_clients(),
+            timeout.as_secs()
+        );
+    }
+
+    // Holding the lock also waits for any write that's still in progress
+    shared.ctx.engine.lock().await.sync().await?;
+    if let Some(unix_socket) = &unix_socket {
+        let _ = unix::remove(std::path::Path::new(&unix_socket.path));
+    }
+
+    tracing::info!("Server stopped");
+    Ok(())
+}
+
+/// Runs the accept loops until all of them finish, or one of them fails.
+async fn serve(accept_loops: &mut JoinSet<anyhow::Result<()>>) -> anyhow::Result<()> {
+    while let Some(result) = accept_loops.join_next().await {
+        result??;
     }
+    Ok(())
 }
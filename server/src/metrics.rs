@@ -0,0 +1,128 @@
+use anyhow::anyhow;
+use core::metrics::{self, METRICS};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::connection::Shared;
+
+/// Largest request head read from a scraper. Anything longer is rejected.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+pub async fn bind(address: &str) -> anyhow::Result<TcpListener> {
+    let address: SocketAddr = address
+        .parse()
+        .map_err(|_| anyhow!("Invalid metrics address '{}'", address))?;
+    TcpListener::bind(address)
+        .await
+        .map_err(|e| anyhow!("Couldn't bind to {}: {}", address, e))
+}
+
+/// Serves the metrics over HTTP until the server shuts down. Only
+/// `GET /metrics` is answered, one request per connection.
+pub async fn accept(listener: TcpListener, shared: Arc<Shared>) -> anyhow::Result<()> {
+    let mut shutdown = shared.shutdown.clone();
+    loop {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stopping| *stopping) => return Ok(()),
+        };
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(socket, &shared).await {
+                tracing::debug!(client = %peer, error = %e, "Failed to serve metrics");
+            }
+        });
+    }
+}
+
+async fn serve(mut socket: TcpStream, shared: &Shared) -> anyhow::Result<()> {
+    let read_timeout = shared.limits().read_timeout();
+    let head = match read_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read_head(&mut socket)).await??,
+        None => read_head(&mut socket).await?,
+    };
+
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = render(shared).await?;
+            response("200 OK", "text/plain; version=0.0.4", &body)
+        }
+        (Some("GET"), Some(_)) => response("404 Not Found", "text/plain", "not found\n"),
+        _ => response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n",
+        ),
+    };
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await?;
+    Ok(())
+}
+
+/// Reads the request line and headers. The body, if any, is ignored.
+async fn read_head(socket: &mut TcpStream) -> anyhow::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_SIZE {
+            return Err(anyhow!("request too large"));
+        }
+        let read = socket.read(&mut buf).await?;
+        if read == 0 {
+            return Err(anyhow!("connection closed before the end of the request"));
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Renders the counters and histograms, and measures the gauges.
+async fn render(shared: &Shared) -> anyhow::Result<String> {
+    let stats = shared.ctx.engine.lock().await.stats().await?;
+    let mut out = String::new();
+    METRICS.render(&mut out);
+    let gauges = [
+        (
+            "tuna_connected_clients",
+            "Clients currently connected",
+            shared.connected_clients() as u64,
+        ),
+        (
+            "tuna_file_size_bytes",
+            "Size of the database file",
+            stats.file_size,
+        ),
+        (
+            "tuna_live_bytes",
+            "Bytes of the database file holding the current value of a key",
+            stats.live_bytes,
+        ),
+        (
+            "tuna_dead_bytes",
+            "Bytes of the database file holding overwritten or deleted values",
+            stats.dead_bytes,
+        ),
+        (
+            "tuna_index_entries",
+            "Keys whose offset is in the index",
+            stats.index_entries,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        metrics::render_gauge(&mut out, name, help, value);
+    }
+    Ok(out)
+}
@@ -0,0 +1,76 @@
+use core::config;
+use core::response::{ErrorCode, Response, ResponseError};
+
+use crate::connection::Shared;
+use crate::logging;
+
+/// Returns the settings whose name matches the pattern.
+pub fn get(shared: &Shared, pattern: &str) -> Response {
+    match shared.config.read().unwrap().get(pattern) {
+        Ok(parameters) => Response::parameters(parameters.into_iter().collect()),
+        Err(e) => Response::error(ResponseError::new(ErrorCode::Internal, e.to_string())),
+    }
+}
+
+/// Changes a setting of the running server. It's only kept in memory until
+/// the config is rewritten.
+pub fn set(shared: &Shared, parameter: &str, value: &str) -> Response {
+    let mut config = shared.config.write().unwrap();
+    let mut updated = config.clone();
+    if let Err(e) = updated.set(parameter, value) {
+        return Response::error(e);
+    }
+    let mut file_config = shared.file_config.lock().unwrap();
+    let mut updated_file = file_config.clone();
+    if let Err(e) = updated_file.set(parameter, value) {
+        return Response::error(e);
+    }
+    if let Err(e) = resize_clients(
+        shared,
+        config.limits.max_clients,
+        updated.limits.max_clients,
+    ) {
+        return Response::error(e);
+    }
+    logging::set_level(&shared.log_level, logging::level(&updated));
+    shared.slowlog.truncate(updated.slowlog.max_len);
+    *config = updated;
+    *file_config = updated_file;
+    tracing::info!("Set {} to {}", parameter, value);
+    Response::ok(None)
+}
+
+/// Writes the settings changed with CONFIG SET to the config file. The ones
+/// overridden on the command line are saved with their value in the file.
+pub fn rewrite(shared: &Shared) -> Response {
+    let config = shared.file_config.lock().unwrap().clone();
+    match config::save(&config) {
+        Ok(()) => {
+            tracing::info!("Rewrote the config file");
+            Response::ok(None)
+        }
+        Err(e) => Response::error(ResponseError::new(ErrorCode::Io, e.to_string())),
+    }
+}
+
+/// Adds or removes permits so `to` clients can be connected. Clients that
+/// are already connected aren't disconnected, so the limit can't be lowered
+/// below their number.
+fn resize_clients(shared: &Shared, from: usize, to: usize) -> Result<(), ResponseError> {
+    if to >= from {
+        shared.clients.add_permits(to - from);
+        return Ok(());
+    }
+    let forgotten = shared.clients.forget_permits(from - to);
+    if forgotten < from - to {
+        shared.clients.add_permits(forgotten);
+        return Err(ResponseError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "limits.max_clients can't be lower than the {} connected clients",
+                from - shared.clients.available_permits()
+            ),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Waits until the process is asked to stop with SIGTERM or SIGINT and
+/// returns the name of the signal.
+pub async fn signal_received() -> anyhow::Result<&'static str> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = sigterm.recv() => Ok("SIGTERM"),
+        _ = sigint.recv() => Ok("SIGINT"),
+    }
+}
@@ -1,15 +1,116 @@
-use std::net::TcpListener;
+use anyhow::anyhow;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 
-/// Check if the local port is available.
+use crate::connection::{self, Shared};
+
+/// Parses a bind address. Both `ip` and `ip:port` forms are accepted (IPv6
+/// addresses with a port must be written as `[ip]:port`); when no port is
+/// given the default one is used.
 ///
 /// # Example
-/// ```no_run
-/// use port_scanner::local_port_available;
-/// println!("Is port 80 available to use? {}", local_port_available(80));
+/// Not run as a doctest, as the server is a binary crate. The tests below
+/// cover it.
+/// ```ignore
+/// let addr = parse_bind_address("::1", 5880)?;
+/// assert_eq!(addr.to_string(), "[::1]:5880");
 /// ```
-pub fn local_port_available(port: u16) -> bool {
-    match TcpListener::bind(("127.0.0.1", port)) {
-        Ok(_) => true,
-        Err(_) => false,
+pub fn parse_bind_address(address: &str, default_port: u16) -> anyhow::Result<SocketAddr> {
+    if let Ok(addr) = address.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    match address.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, default_port)),
+        Err(_) => Err(anyhow!("Invalid bind address '{}'", address)),
+    }
+}
+
+/// Binds a listener for every address, failing on the first one that
+/// can't be bound.
+pub async fn bind_all(addresses: &[SocketAddr]) -> anyhow::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| anyhow!("Couldn't bind to {}: {}", address, e))?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+/// Accepts TCP connections, serving each one over TLS if there's an acceptor,
+/// until the server shuts down.
+pub async fn accept(
+    listener: TcpListener,
+    tls_acceptor: Option<TlsAcceptor>,
+    shared: Arc<Shared>,
+) -> anyhow::Result<()> {
+    let mut shutdown = shared.shutdown.clone();
+    loop {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stopping| *stopping) => return Ok(()),
+        };
+        let permit = connection::admit(&shared);
+        let shared = shared.clone();
+        match tls_acceptor.clone() {
+            Some(tls_acceptor) => {
+                // The error can't be sent before the handshake, and the
+                // handshakes of rejected clients aren't worth the work
+                let Some(permit) = permit else {
+                    tracing::warn!(client = %peer, "Rejected connection: too many clients");
+                    continue;
+                };
+                tokio::spawn(async move {
+                    // The handshake runs in the connection task so a slow client
+                    // can't hold up the accept loop
+                    let timeout = shared.limits().read_timeout();
+                    match connection::with_timeout(timeout, tls_acceptor.accept(socket)).await {
+                        Ok(stream) => {
+                            connection::handle(stream, peer.to_string(), shared, Some(permit)).await
+                        }
+                        Err(e) => {
+                            tracing::warn!(client = %peer, error = %e, "TLS handshake failed")
+                        }
+                    }
+                });
+            }
+            None => {
+                tokio::spawn(connection::handle(socket, peer.to_string(), shared, permit));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(address: &str) -> Option<String> {
+        parse_bind_address(address, 5880)
+            .ok()
+            .map(|addr| addr.to_string())
+    }
+
+    #[test]
+    fn parse_bind_address_uses_the_default_port() {
+        assert_eq!(parse("127.0.0.1").as_deref(), Some("127.0.0.1:5880"));
+        assert_eq!(parse("::1").as_deref(), Some("[::1]:5880"));
+    }
+
+    #[test]
+    fn parse_bind_address_keeps_the_given_port() {
+        assert_eq!(parse("0.0.0.0:6000").as_deref(), Some("0.0.0.0:6000"));
+        assert_eq!(parse("[::1]:6000").as_deref(), Some("[::1]:6000"));
+    }
+
+    #[test]
+    fn parse_bind_address_rejects_host_names() {
+        assert_eq!(parse("localhost"), None);
+        assert_eq!(parse("localhost:6000"), None);
+        // Read as an IPv6 address, as the port isn't in brackets
+        assert_eq!(parse("::1:6000").as_deref(), Some("[::1:6000]:5880"));
     }
 }
@@ -0,0 +1,49 @@
+use anyhow::anyhow;
+use core::config::TlsConfig;
+use std::{fs::File, io::BufReader, sync::Arc};
+use tokio_rustls::rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+
+/// Builds the TLS acceptor for the TCP listeners. Client certificates are
+/// required and verified when a client CA is configured.
+pub fn acceptor(config: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let certs = load_certs(&config.cert_file)?;
+    let key = load_private_key(&config.key_file)?;
+
+    let builder = match &config.client_ca_file {
+        Some(client_ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(client_ca_file)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build()?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let server_config = builder.with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+fn load_certs(path: &str) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(
+        File::open(path).map_err(|e| anyhow!("Couldn't open certificate {}: {}", path, e))?,
+    );
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates found in {}", path));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(
+        File::open(path).map_err(|e| anyhow!("Couldn't open private key {}: {}", path, e))?,
+    );
+    rustls_pemfile::private_key(&mut reader)?.ok_or(anyhow!("No private key found in {}", path))
+}
@@ -0,0 +1,186 @@
+use anyhow::anyhow;
+use core::config::UnixSocketConfig;
+use std::fs::{self, DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::UnixListener;
+
+use crate::connection::{self, Shared};
+
+/// Binds the Unix domain socket and applies the configured permissions.
+/// A socket file left behind by a previous run is replaced, unless there's
+/// still a server listening on it.
+pub fn bind(config: &UnixSocketConfig) -> anyhow::Result<UnixListener> {
+    let path = Path::new(&config.path);
+    let mode = config
+        .permissions
+        .as_ref()
+        .map(|permissions| {
+            u32::from_str_radix(permissions, 8)
+                .map_err(|_| anyhow!("Invalid socket permissions '{}'", permissions))
+        })
+        .transpose()?;
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(anyhow!("{} exists and isn't a socket", config.path));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(anyhow!("Couldn't bind to {}: address in use", config.path));
+        }
+        remove(path)?;
+    }
+
+    // The socket is created with the umask's permissions, so it's bound in a
+    // directory only the server can enter and moved into place once it has
+    // the configured ones. Clients can't connect in between.
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid socket path {}", config.path))?;
+    let dir = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|e| anyhow!("Couldn't bind to {}: {}", config.path, e))?;
+    let bound = bind_in(&dir, path, mode);
+    let _ = fs::remove_dir_all(&dir);
+    bound.map_err(|e| anyhow!("Couldn't bind to {}: {}", config.path, e))
+}
+
+/// Binds the socket in `dir`, then moves it to `path` with the given mode.
+fn bind_in(dir: &Path, path: &Path, mode: Option<u32>) -> std::io::Result<UnixListener> {
+    let bound_path = dir.join("socket");
+    let listener = UnixListener::bind(&bound_path)?;
+    if let Some(mode) = mode {
+        fs::set_permissions(&bound_path, Permissions::from_mode(mode))?;
+    }
+    fs::rename(&bound_path, path)?;
+    Ok(listener)
+}
+
+/// Removes the socket file, leaving the path alone if something other than
+/// a socket took its place.
+pub fn remove(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Accepts connections on the Unix domain socket until the server shuts down.
+/// Clients are named after `path`, as the listener was bound elsewhere.
+pub async fn accept(
+    listener: UnixListener,
+    path: String,
+    shared: Arc<Shared>,
+) -> anyhow::Result<()> {
+    let mut shutdown = shared.shutdown.clone();
+
+    loop {
+        let (socket, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stopping| *stopping) => return Ok(()),
+        };
+        tokio::spawn(connection::handle(
+            socket,
+            format!("unix:{}", path),
+            shared.clone(),
+            connection::admit(&shared),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    /// Directory of a test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("tuna-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(path: &Path, permissions: Option<&str>) -> UnixSocketConfig {
+        UnixSocketConfig {
+            path: path.display().to_string(),
+            permissions: permissions.map(|p| p.to_string()),
+        }
+    }
+
+    /// Entries of the directory, to check nothing is left behind.
+    fn entries(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn bind_applies_the_configured_permissions() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _runtime = runtime.enter();
+        let dir = TempDir::new("unix-permissions");
+        let path = dir.0.join("tuna.sock");
+
+        let _listener = bind(&config(&path, Some("600"))).unwrap();
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.mode() & 0o777, 0o600);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+        assert_eq!(entries(&dir.0), ["tuna.sock"]);
+    }
+
+    #[test]
+    fn bind_replaces_stale_sockets() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _runtime = runtime.enter();
+        let dir = TempDir::new("unix-stale");
+        let path = dir.0.join("tuna.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let _listener = bind(&config(&path, Some("660"))).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o660);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+    }
+
+    #[test]
+    fn bind_leaves_other_files_alone() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _runtime = runtime.enter();
+        let dir = TempDir::new("unix-file");
+        let path = dir.0.join("tuna.sock");
+        fs::write(&path, "data").unwrap();
+
+        assert!(bind(&config(&path, None)).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+        remove(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+    }
+
+    #[test]
+    fn bind_rejects_invalid_permissions() {
+        let dir = TempDir::new("unix-invalid");
+        let path = dir.0.join("tuna.sock");
+
+        assert!(bind(&config(&path, Some("rw-------"))).is_err());
+        assert!(entries(&dir.0).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! Runs the server binary for the integration tests, each instance with its
+//! own home directory and port.
+// Each test file only uses some of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as Process, Stdio};
+use std::time::Duration;
+
+use core::command::Command;
+use core::frame;
+use core::response::Response;
+use core::serializer::{
+    CommandSerializer, ProtoCommandSerializer, ProtoResponseSerializer, ResponseSerializer,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+/// Server process, killed when dropped along with its home directory.
+pub struct Server {
+    process: Child,
+    pub home: PathBuf,
+    pub port: u16,
+}
+
+impl Server {
+    /// Starts the server with the config file `config`, which is given the
+    /// database file. `configure` can write files to the home directory and
+    /// add arguments before the server is started.
+    pub fn start(config: &str, configure: impl FnOnce(&Path, &mut Process)) -> Server {
+        let port = free_port();
+        let home = std::env::temp_dir().join(format!("tuna-test-{}-{}", std::process::id(), port));
+        let config_dir = home.join(".config").join("tunadb");
+        fs::create_dir_all(&config_dir).unwrap();
+        let config = format!(
+            "file_path = {:?}\n{}",
+            home.join("tuna.db").display().to_string(),
+            config
+        );
+        write(&config_dir, "config.toml", &config);
+
+        let mut process = Process::new(env!("CARGO_BIN_EXE_tuna-server"));
+        process
+            .env("HOME", &home)
+            .env_remove("RUST_LOG")
+            .args(["--port", &port.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        configure(&home, &mut process);
+        Server {
+            process: process.spawn().unwrap(),
+            home,
+            port,
+        }
+    }
+
+    /// Connects once the server is listening.
+    pub async fn connect(&self) -> TcpStream {
+        for _ in 0..100 {
+            if let Ok(socket) = TcpStream::connect(("127.0.0.1", self.port)).await {
+                return socket;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("the server didn't start listening");
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+pub fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Runs a test on a new runtime. `#[tokio::test]` can't be used, as the
+/// `core` crate shadows the one its expansion refers to.
+pub fn run(test: impl std::future::Future<Output = ()>) {
+    tokio::runtime::Runtime::new().unwrap().block_on(test)
+}
+
+/// Sends a command without waiting for its response.
+pub async fn send<S>(stream: &mut S, cmd: &Command) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::new();
+    ProtoCommandSerializer.encode(cmd, &mut buf).unwrap();
+    frame::write_frame(stream, &buf).await
+}
+
+/// Reads the next response, or the error that broke the connection.
+pub async fn receive<S>(stream: &mut S) -> std::io::Result<Response>
+where
+    S: AsyncRead + Unpin,
+{
+    // The tests trust the server they started with any response size
+    let bytes = frame::read_frame(stream, usize::MAX)
+        .await?
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "connection closed")
+        })?;
+    Ok(ProtoResponseSerializer.decode(&bytes).unwrap())
+}
+
+/// Sends a command and returns its response.
+pub async fn request<S>(stream: &mut S, cmd: &Command) -> std::io::Result<Response>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    send(stream, cmd).await?;
+    receive(stream).await
+}
+
+/// Sends a PING and returns the reply, or the error that broke the connection.
+pub async fn ping<S>(stream: &mut S) -> std::io::Result<String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let response = request(stream, &Command::Ping { message: None }).await?;
+    assert!(response.error.is_none());
+    Ok(response.content.unwrap_or_default())
+}
@@ -0,0 +1,55 @@
+//! Runs the server with tight connection limits and checks clients that
+//! misbehave don't keep their slot.
+use std::time::Duration;
+
+use core::command::Command;
+
+mod common;
+
+use common::{receive, request, run, send, Server};
+
+const LIMITS: &str = "
+[limits]
+max_clients = 1
+write_timeout_secs = 1
+";
+
+#[test]
+fn frees_the_slot_of_clients_that_stop_reading() {
+    run(async {
+        let server = Server::start(LIMITS, |_, _| {});
+
+        let mut client = server.connect().await;
+        let set = Command::Set {
+            key: "key".to_string(),
+            value: "v".repeat(60_000),
+        };
+        assert!(request(&mut client, &set).await.unwrap().error.is_none());
+        // Far more than the socket buffers hold, and the responses are never read
+        for _ in 0..1000 {
+            let get = Command::Get {
+                key: "key".to_string(),
+            };
+            send(&mut client, &get).await.unwrap();
+        }
+
+        // The slot is taken until the server gives up on writing to the client
+        let mut waited = Duration::ZERO;
+        loop {
+            let mut other = server.connect().await;
+            let response = request(&mut other, &Command::Ping { message: None }).await;
+            if matches!(response, Ok(response) if response.error.is_none()) {
+                break;
+            }
+            assert!(waited < Duration::from_secs(10), "the slot wasn't freed");
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            waited += Duration::from_millis(200);
+        }
+
+        // The client that stopped reading was disconnected
+        let mut closed = false;
+        while !closed {
+            closed = receive(&mut client).await.is_err();
+        }
+    });
+}
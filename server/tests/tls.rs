@@ -0,0 +1,153 @@
+//! Runs the server with TLS and talks to it over TLS, with and without a
+//! client certificate. The certificates are generated for each test.
+use std::sync::Arc;
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::{PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+mod common;
+
+use common::{ping, run, write, Server};
+
+/// Certificate signed by the test CA, with its private key.
+struct Issued {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+/// CA signing both the server and the client certificates.
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Ca {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let cert = params.self_signed(&key).unwrap();
+        Ca { cert, key }
+    }
+
+    fn issue(&self, name: &str) -> Issued {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec![name.to_string()])
+            .unwrap()
+            .signed_by(&key, &self.cert, &self.key)
+            .unwrap();
+        Issued { cert, key }
+    }
+}
+
+/// Starts the server with the CA's certificate for `localhost`, requiring
+/// client certificates signed by the CA if `mtls` is set.
+fn start(ca: &Ca, mtls: bool) -> Server {
+    Server::start("", |home, process| {
+        let server = ca.issue("localhost");
+        let cert_file = write(home, "server.pem", &server.cert.pem());
+        let key_file = write(home, "server.key", &server.key.serialize_pem());
+        process
+            .arg("--tls-cert")
+            .arg(&cert_file)
+            .arg("--tls-key")
+            .arg(&key_file);
+        if mtls {
+            let ca_file = write(home, "ca.pem", &ca.cert.pem());
+            process.arg("--tls-client-ca").arg(&ca_file);
+        }
+    })
+}
+
+/// Connects over TLS once the server is listening, presenting the client
+/// certificate if there's one.
+async fn connect(
+    server: &Server,
+    ca: &Ca,
+    client: Option<&Issued>,
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.add(ca.cert.der().clone()).unwrap();
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let config = match client {
+        Some(client) => builder
+            .with_client_auth_cert(
+                vec![client.cert.der().clone()],
+                PrivateKeyDer::try_from(client.key.serialize_der()).unwrap(),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+
+    let socket = server.connect().await;
+    TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), socket)
+        .await
+}
+
+#[test]
+fn serves_clients_over_tls() {
+    run(async {
+        let ca = Ca::new();
+        let server = start(&ca, false);
+
+        let mut stream = connect(&server, &ca, None).await.unwrap();
+        assert_eq!(ping(&mut stream).await.unwrap(), "PONG");
+    });
+}
+
+#[test]
+fn rejects_untrusted_servers() {
+    run(async {
+        let ca = Ca::new();
+        let server = start(&ca, false);
+
+        assert!(connect(&server, &Ca::new(), None).await.is_err());
+    });
+}
+
+#[test]
+fn serves_clients_with_a_certificate_over_mtls() {
+    run(async {
+        let ca = Ca::new();
+        let server = start(&ca, true);
+
+        let client = ca.issue("client");
+        let mut stream = connect(&server, &ca, Some(&client)).await.unwrap();
+        assert_eq!(ping(&mut stream).await.unwrap(), "PONG");
+    });
+}
+
+#[test]
+fn rejects_clients_without_a_certificate_over_mtls() {
+    run(async {
+        let ca = Ca::new();
+        let server = start(&ca, true);
+
+        // With TLS 1.3 the server checks the client certificate after the client
+        // considers the handshake done, so the failure shows on the first request
+        let result = match connect(&server, &ca, None).await {
+            Ok(mut stream) => ping(&mut stream).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        assert!(result.is_err());
+    });
+}
+
+#[test]
+fn rejects_clients_with_an_untrusted_certificate_over_mtls() {
+    run(async {
+        let ca = Ca::new();
+        let server = start(&ca, true);
+
+        let client = Ca::new().issue("client");
+        let result = match connect(&server, &ca, Some(&client)).await {
+            Ok(mut stream) => ping(&mut stream).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        assert!(result.is_err());
+    });
+}
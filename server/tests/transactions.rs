@@ -0,0 +1,109 @@
+//! Runs transactions while another client writes the keys they watch.
+use core::command::Command;
+use core::response::{ErrorCode, Response};
+use tokio::net::TcpStream;
+
+mod common;
+
+use common::{request, run, Server};
+
+fn set(key: &str, value: &str) -> Command {
+    Command::Set {
+        key: key.to_string(),
+        value: value.to_string(),
+    }
+}
+
+async fn ok(stream: &mut TcpStream, cmd: &Command) -> Response {
+    let response = request(stream, cmd).await.unwrap();
+    assert!(response.error.is_none(), "{:?}", response.error);
+    response
+}
+
+async fn get(stream: &mut TcpStream, key: &str) -> Option<String> {
+    let get = Command::Get {
+        key: key.to_string(),
+    };
+    request(stream, &get).await.unwrap().content
+}
+
+/// Runs MULTI, SET `key` = `value` and EXEC.
+async fn set_in_transaction(stream: &mut TcpStream, key: &str, value: &str) -> Response {
+    ok(stream, &Command::Multi).await;
+    ok(stream, &set(key, value)).await;
+    request(stream, &Command::Exec).await.unwrap()
+}
+
+fn watch(key: &str) -> Command {
+    Command::Watch {
+        keys: vec![key.to_string()],
+    }
+}
+
+#[test]
+fn exec_aborts_after_another_client_writes_a_watched_key() {
+    run(async {
+        let server = Server::start("", |_, _| {});
+        let mut client = server.connect().await;
+        let mut other = server.connect().await;
+
+        ok(&mut client, &watch("key")).await;
+        ok(&mut other, &set("key", "other")).await;
+
+        let response = set_in_transaction(&mut client, "key", "client").await;
+        assert_eq!(response.error.unwrap().code, ErrorCode::Aborted);
+        assert_eq!(get(&mut client, "key").await.as_deref(), Some("other"));
+    });
+}
+
+#[test]
+fn exec_runs_when_the_watched_keys_are_unchanged() {
+    run(async {
+        let server = Server::start("", |_, _| {});
+        let mut client = server.connect().await;
+        let mut other = server.connect().await;
+
+        ok(&mut client, &watch("key")).await;
+        ok(&mut other, &set("other-key", "other")).await;
+
+        let response = set_in_transaction(&mut client, "key", "client").await;
+        assert!(response.error.is_none());
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(get(&mut other, "key").await.as_deref(), Some("client"));
+    });
+}
+
+#[test]
+fn unwatch_clears_the_watched_keys() {
+    run(async {
+        let server = Server::start("", |_, _| {});
+        let mut client = server.connect().await;
+        let mut other = server.connect().await;
+
+        ok(&mut client, &watch("key")).await;
+        ok(&mut client, &Command::Unwatch).await;
+        ok(&mut other, &set("key", "other")).await;
+
+        let response = set_in_transaction(&mut client, "key", "client").await;
+        assert!(response.error.is_none());
+        assert_eq!(get(&mut other, "key").await.as_deref(), Some("client"));
+    });
+}
+
+#[test]
+fn discard_clears_the_watched_keys() {
+    run(async {
+        let server = Server::start("", |_, _| {});
+        let mut client = server.connect().await;
+        let mut other = server.connect().await;
+
+        ok(&mut client, &watch("key")).await;
+        ok(&mut client, &Command::Multi).await;
+        ok(&mut client, &Command::Discard).await;
+        ok(&mut other, &set("key", "other")).await;
+
+        let response = set_in_transaction(&mut client, "key", "client").await;
+        assert!(response.error.is_none());
+        assert_eq!(get(&mut other, "key").await.as_deref(), Some("client"));
+    });
+}